@@ -0,0 +1,80 @@
+use crate::fractional_type::FractionalType;
+use crate::math::Vec3;
+use num_traits::identities::Zero;
+
+/// Bounds of a model, computed once at load time.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Bounds
+{
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl Bounds
+{
+    /// The bounds of all `points`, or None if there are none.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3> + Clone) -> Option<Self>
+    {
+        let aabb = Aabb::from_points(points.clone())?;
+        Some(Self { aabb, sphere: BoundingSphere::around(aabb.center(), points) })
+    }
+}
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Aabb
+{
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb
+{
+    /// The smallest box containing all `points`, or None if there are none.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3>) -> Option<Self>
+    {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+        Some(points.fold(Self { min: first, max: first }, |aabb, point| aabb.including(point)))
+    }
+
+    /// The box grown to contain `point`.
+    pub fn including(self, point: &Vec3) -> Self
+    {
+        Self {
+            min: Vec3::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z)),
+            max: Vec3::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z)),
+        }
+    }
+
+    pub fn center(&self) -> Vec3
+    {
+        (self.min + self.max) / FractionalType::from_num(2)
+    }
+
+    pub fn size(&self) -> Vec3
+    {
+        self.max - self.min
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BoundingSphere
+{
+    pub center: Vec3,
+    pub radius: FractionalType,
+}
+
+impl BoundingSphere
+{
+    /// The smallest sphere around `center` containing all `points`. Centered
+    /// on the box center this is not the tightest sphere possible, but close
+    /// enough for framing and culling.
+    pub fn around<'a>(center: Vec3, points: impl IntoIterator<Item = &'a Vec3>) -> Self
+    {
+        let radius = points.into_iter()
+            .map(|point| (*point - center).length())
+            .fold(FractionalType::zero(), FractionalType::max);
+        Self { center, radius }
+    }
+}
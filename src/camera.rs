@@ -1,15 +1,601 @@
-struct Camera
+use crate::bounds::BoundingSphere;
+use crate::fractional_type::{self, FractionalType};
+use crate::gte;
+use crate::math::{Mat3, Mat3x4, Quat, Vec2, Vec3};
+use crate::projection::{Projection, ProjectionMode};
+use num_traits::identities::{One, Zero};
+use std::cell::{Cell, RefCell};
+
+pub mod orbit;
+
+// the GTE works on integer coordinates, world units are scaled by this first
+pub const GTE_UNITS_PER_WORLD_UNIT: i32 = 256;
+// a horizontal field of view of 90 degrees
+pub const DEFAULT_FOV: i32 = fractional_type::ANGLE_QUARTER_TURN;
+
+// frame() treats smaller spheres as this big, in world units
+const FRAME_MIN_RADIUS: f32 = 0.0625;
+// frame() keeps the near plane at least this fraction of the distance away
+const FRAME_MAX_DEPTH_RANGE: i32 = 64;
+
+/// How the perspective divide is computed
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DivideMode
+{
+    /// plain FractionalType division
+    Exact,
+    /// the GTE's reciprocal approximation, with its saturation and clamping
+    Hardware,
+}
+
+// https://www.cs.cmu.edu/~16385/s17/Slides/11.1_Camera_matrix.pdf
+// P = K * R * [I | -C]
+// where P is the camera projection, K is the 3x3 intrinsics (see
+// projection::Projection), R is the 3D rotation matrix, -C is the negative 3D
+// translation
+pub struct Camera
 {
-    pub position: [fractional_type::FractionalType; 3],
-    pub rotation: [fractional_type::FractionalType; 3],
-    pub fov: fractional_type::FractionalType,
+    position: Vec3,
+    // rotates camera space into world space, so R is its inverse. Turning is
+    // always relative to the camera's own axes, which avoids gimbal lock and
+    // lets the camera roll and pitch past vertical.
+    orientation: Quat,
+    projection: Projection,
+
+    pub divide_mode: DivideMode,
+    // when set, vertices are projected by the emulated GTE instead of the
+    // camera matrix
+    pub use_gte: bool,
 
-    intrinsic_matrix: rulinalg::matrix::Matrix<fractional_type::FractionalType>,
-    rotation_matrix: rulinalg::matrix::Matrix<fractional_type::FractionalType>,
-    translation_matrix: rulinalg::matrix::Matrix<fractional_type::FractionalType>,
+    // rebuilt on demand after any of the above change
+    intrinsic_matrix: Cell<Option<Mat3x4>>,
+    rotation_matrix: Cell<Option<Mat3>>,
+    translation_matrix: Cell<Option<Mat3x4>>,
+    camera_matrix: Cell<Option<Mat3x4>>,
+    gte: RefCell<gte::Gte>,
+    gte_loaded: Cell<bool>,
 }
 
-// impl Camera
-// {
-//     fn rot_left_right()
-// }
\ No newline at end of file
+impl Camera
+{
+    pub fn new(position: Vec3, resolution: (u32, u32)) -> Self
+    {
+        Self {
+            position,
+            orientation: Quat::identity(),
+            projection: Projection::from_fov(DEFAULT_FOV, resolution),
+            divide_mode: DivideMode::Exact,
+            use_gte: false,
+            intrinsic_matrix: Cell::new(None),
+            rotation_matrix: Cell::new(None),
+            translation_matrix: Cell::new(None),
+            camera_matrix: Cell::new(None),
+            gte: RefCell::new(gte::Gte::new()),
+            gte_loaded: Cell::new(false),
+        }
+    }
+
+    /// A camera at `position` that faces `target`.
+    pub fn looking_at(position: Vec3, target: Vec3, resolution: (u32, u32)) -> Self
+    {
+        let mut camera = Self::new(position, resolution);
+        camera.look_at(target);
+        camera
+    }
+
+    ///////////////
+    //  GETTERS  //
+    ///////////////
+
+    pub fn position(&self) -> Vec3
+    {
+        self.position
+    }
+
+    pub fn orientation(&self) -> Quat
+    {
+        self.orientation
+    }
+
+    /// The direction the camera looks in, in world space.
+    pub fn forward(&self) -> Vec3
+    {
+        self.orientation.rotate(Vec3::new(FractionalType::zero(), FractionalType::zero(), FractionalType::one()))
+    }
+
+    pub fn projection(&self) -> &Projection
+    {
+        &self.projection
+    }
+
+    /// Horizontal field of view, in fractional_type::ANGLE_ONE_TURN units.
+    pub fn fov(&self) -> i32
+    {
+        self.projection.fov()
+    }
+
+    pub fn resolution(&self) -> (u32, u32)
+    {
+        self.projection.resolution
+    }
+
+    ////////////////
+    //  CONTROLS  //
+    ////////////////
+
+    pub fn set_position(&mut self, position: Vec3)
+    {
+        self.position = position;
+        self.invalidate_extrinsics();
+    }
+
+    /// Moves by `offset` given in world space.
+    pub fn translate(&mut self, offset: Vec3)
+    {
+        self.set_position(self.position + offset);
+    }
+
+    /// Moves by `offset` given in camera space: +x is right, +y is up and +z
+    /// is forward.
+    pub fn translate_local(&mut self, offset: Vec3)
+    {
+        let world_offset = self.orientation.rotate(offset);
+        self.translate(world_offset);
+    }
+
+    pub fn set_orientation(&mut self, orientation: Quat)
+    {
+        self.orientation = orientation.normalize();
+        self.invalidate_extrinsics();
+    }
+
+    /// Sets the orientation from Euler angles in fractional_type::ANGLE_ONE_TURN
+    /// units, applied as yaw about y, then pitch about x, then roll about z.
+    pub fn set_rotation(&mut self, yaw: i32, pitch: i32, roll: i32)
+    {
+        self.set_orientation(Quat::rotation_y(-yaw) * Quat::rotation_x(-pitch) * Quat::rotation_z(-roll));
+    }
+
+    /// Turns by `rotation` given in camera space.
+    pub fn rotate_local(&mut self, rotation: Quat)
+    {
+        // renormalized by set_orientation so repeated turns don't drift
+        self.set_orientation(self.orientation * rotation);
+    }
+
+    /// Turns left for a positive angle, about the camera's up axis.
+    pub fn rotate_yaw(&mut self, angle: i32)
+    {
+        self.rotate_local(Quat::rotation_y(-angle));
+    }
+
+    /// Turns up for a positive angle, about the camera's right axis.
+    pub fn rotate_pitch(&mut self, angle: i32)
+    {
+        self.rotate_local(Quat::rotation_x(-angle));
+    }
+
+    /// Rolls clockwise for a positive angle, about the view direction, which
+    /// turns the image counterclockwise.
+    pub fn rotate_roll(&mut self, angle: i32)
+    {
+        self.rotate_local(Quat::rotation_z(-angle));
+    }
+
+    /// Turns the camera to face `target`, leaving it without roll.
+    pub fn look_at(&mut self, target: Vec3)
+    {
+        let direction = target - self.position;
+        // yaw brings the direction into the y-z plane, then pitch onto the z axis
+        let yaw = fractional_type::atan2(-direction.x, direction.z);
+        let horizontal = Vec3::new(direction.x, FractionalType::zero(), direction.z).length();
+        let pitch = fractional_type::atan2(direction.y, horizontal);
+        self.set_rotation(yaw, pitch, 0);
+    }
+
+    /// Sets the horizontal field of view, in fractional_type::ANGLE_ONE_TURN
+    /// units. It is kept strictly between zero and half a turn.
+    pub fn set_fov(&mut self, fov: i32)
+    {
+        let mut projection = self.projection;
+        projection.set_fov(fov);
+        self.set_projection(projection);
+    }
+
+    /// Changes the resolution, keeping the field of view.
+    pub fn set_resolution(&mut self, resolution: (u32, u32))
+    {
+        let mut projection = self.projection;
+        projection.set_resolution(resolution);
+        self.set_projection(projection);
+    }
+
+    /// Moves the camera back along its view direction until `sphere` fills
+    /// the view, and fits the near and far planes around it. An orthographic
+    /// projection is scaled instead.
+    pub fn frame(&mut self, sphere: &BoundingSphere)
+    {
+        let mut projection = self.projection;
+        // the narrower half of the screen decides how far back we have to be,
+        // measured in pixels as tall as the framebuffer's since that's what H
+        // is in
+        let half_extent = FractionalType::from_num(projection.square_width().min(projection.resolution.1) / 2);
+        let radius = sphere.radius.max(FractionalType::from_num(FRAME_MIN_RADIUS));
+        // the sphere touches both sides of the view when
+        // sin(half fov) = radius / distance, where tan(half fov) = extent / H
+        let ratio = projection.h / half_extent;
+        let distance = radius * (FractionalType::one() + ratio * ratio).sqrt();
+
+        projection.near = (distance - radius * FractionalType::from_num(2)).max(distance / FractionalType::from_num(FRAME_MAX_DEPTH_RANGE));
+        projection.far = distance + radius * FractionalType::from_num(2);
+        projection.scale = half_extent / radius;
+        self.set_projection(projection);
+        self.set_position(sphere.center - self.forward() * distance);
+    }
+
+    pub fn set_projection_mode(&mut self, mode: ProjectionMode)
+    {
+        let mut projection = self.projection;
+        projection.mode = mode;
+        self.set_projection(projection);
+    }
+
+    pub fn set_projection(&mut self, projection: Projection)
+    {
+        self.projection = projection;
+        self.intrinsic_matrix.set(None);
+        self.camera_matrix.set(None);
+        self.gte_loaded.set(false);
+    }
+
+    fn invalidate_extrinsics(&mut self)
+    {
+        self.rotation_matrix.set(None);
+        self.translation_matrix.set(None);
+        self.camera_matrix.set(None);
+        self.gte_loaded.set(false);
+    }
+
+    ////////////////
+    //  MATRICES  //
+    ////////////////
+
+    pub fn intrinsic_matrix(&self) -> Mat3x4
+    {
+        if let Some(matrix) = self.intrinsic_matrix.get()
+        {
+            return matrix;
+        }
+        let matrix = self.projection.intrinsic_matrix();
+        self.intrinsic_matrix.set(Some(matrix));
+        matrix
+    }
+
+    pub fn rotation_matrix(&self) -> Mat3
+    {
+        if let Some(matrix) = self.rotation_matrix.get()
+        {
+            return matrix;
+        }
+        let matrix = self.orientation.conjugate().to_mat3();
+        self.rotation_matrix.set(Some(matrix));
+        matrix
+    }
+
+    pub fn translation_matrix(&self) -> Mat3x4
+    {
+        if let Some(matrix) = self.translation_matrix.get()
+        {
+            return matrix;
+        }
+        let matrix = Mat3x4::from_translation(-self.position);
+        self.translation_matrix.set(Some(matrix));
+        matrix
+    }
+
+    /// P = K * R * [I | -C]
+    pub fn camera_matrix(&self) -> Mat3x4
+    {
+        if let Some(matrix) = self.camera_matrix.get()
+        {
+            return matrix;
+        }
+        let matrix = self.intrinsic_matrix() * (self.rotation_matrix() * self.translation_matrix());
+        self.camera_matrix.set(Some(matrix));
+        matrix
+    }
+
+    //////////////////
+    //  PROJECTION  //
+    //////////////////
+
+    /// Camera space depth of a world space position, the distance in front of
+    /// the camera along its view direction. It is the same in both projection
+    /// modes, so clipping and depth sorting should use it.
+    pub fn depth(&self, position: &Vec3) -> FractionalType
+    {
+        self.rotation_matrix().rows[2].dot(*position - self.position)
+    }
+
+    /// Projects a world space position to image coordinates.
+    pub fn project(&self, position: &Vec3) -> Vec2
+    {
+        if self.use_gte
+        {
+            match self.projection.mode
+            {
+                ProjectionMode::Perspective => self.project_gte(position),
+                ProjectionMode::Orthographic => self.project_gte_orthographic(position),
+            }
+        }
+        else
+        {
+            let im_homogeneous = self.camera_matrix() * position.extend(FractionalType::one());
+            self.homogenous_image_coordinates_to_image_coordinates(&im_homogeneous)
+        }
+    }
+
+    fn project_gte(&self, position: &Vec3) -> Vec2
+    {
+        let mut gte = self.gte.borrow_mut();
+        if !self.gte_loaded.get()
+        {
+            self.load_gte(&mut gte);
+            self.gte_loaded.set(true);
+        }
+        gte.v[0] = position.to_array().map(to_gte_vector);
+        gte.rtps(true, false);
+        let (sx, sy) = gte.screen_xy(2);
+        Vec2::new(FractionalType::from_num(sx), FractionalType::from_num(sy))
+    }
+
+    // RTPS always divides, so an orthographic projection only uses the GTE to
+    // transform into camera space (MVMVA with the rotation and translation)
+    // and scales and offsets the result on the CPU, like games did.
+    fn project_gte_orthographic(&self, position: &Vec3) -> Vec2
+    {
+        let mut gte = self.gte.borrow_mut();
+        if !self.gte_loaded.get()
+        {
+            self.load_gte(&mut gte);
+            self.gte_loaded.set(true);
+        }
+        gte.v[0] = position.to_array().map(to_gte_vector);
+        gte.mvmva(true, false, gte::MvmvaMatrix::Rotation, gte::MvmvaVector::V0, gte::MvmvaTranslation::Translation);
+        // MAC1/MAC2 are camera space in GTE units, with y already flipped
+        Vec2::new(
+            self.projection.offset.x + from_gte_units(gte.mac[1]) * self.projection.scale,
+            self.projection.offset.y + from_gte_units(gte.mac[2]) * self.projection.scale
+        )
+    }
+
+    // Loads the camera into the GTE so that RTPS produces the same screen
+    // coordinates as camera_matrix. The pixel aspect and the y flip of the
+    // intrinsics are folded into the rotation matrix.
+    fn load_gte(&self, gte: &mut gte::Gte)
+    {
+        let axis_scale = Vec3::new(FractionalType::one() / self.projection.pixel_aspect, -FractionalType::one(), FractionalType::one());
+        let rotation = self.rotation_matrix().scale_rows(axis_scale);
+        let translation = rotation * -self.position;
+        for row in 0..3
+        {
+            gte.rotation[row] = rotation.rows[row].to_array().map(gte::to_1_3_12);
+            gte.translation[row] = to_gte_units(translation[row]).clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        }
+        gte.h = self.gte_h();
+        gte.ofx = gte::to_fraction_bits_16(self.projection.offset.x);
+        gte.ofy = gte::to_fraction_bits_16(self.projection.offset.y);
+    }
+
+    fn gte_h(&self) -> u16
+    {
+        self.projection.h.round().to_num::<i32>().clamp(0, u16::MAX as i32) as u16
+    }
+
+    fn homogenous_image_coordinates_to_image_coordinates(&self, homogeneous_coordinates: &Vec3) -> Vec2
+    {
+        if self.projection.mode == ProjectionMode::Orthographic
+        {
+            // w is always one
+            Vec2::new(homogeneous_coordinates.x, homogeneous_coordinates.y)
+        }
+        else if self.divide_mode == DivideMode::Hardware
+        {
+            self.hardware_divide(homogeneous_coordinates)
+        }
+        else if homogeneous_coordinates.z == FractionalType::zero()
+        {
+            Vec2::new(
+                to_proper_inf(homogeneous_coordinates.x),
+                to_proper_inf(homogeneous_coordinates.y)
+            )
+        }
+        else
+        {
+            Vec2::new(
+                homogeneous_coordinates.x / homogeneous_coordinates.z,
+                homogeneous_coordinates.y / homogeneous_coordinates.z
+            )
+        }
+    }
+
+    // Emulates RTPS's H / SZ3 for the perspective divide. The homogeneous w is
+    // camera space Z, which is SZ3 once scaled to GTE units, so
+    // 1 / w = (H / SZ3) * GTE_UNITS_PER_WORLD_UNIT / H.
+    fn hardware_divide(&self, homogeneous_coordinates: &Vec3) -> Vec2
+    {
+        let h = self.gte_h();
+        // far depths saturate SZ3 like MAC3 >> 12 does in RTPS, and an
+        // overflow saturates the quotient, which already puts the vertex on
+        // the near plane like the hardware does
+        let sz3 = to_gte_units(homogeneous_coordinates.z).clamp(0, u16::MAX as i64) as u16;
+        let (quotient, _overflow) = gte::divide(h, sz3);
+        // scaled in 1.16 before the conversion, small quotients would lose
+        // most of their bits to a 12 bit fraction first
+        let reciprocal = FractionalType::from_num(
+            fixed::types::U16F16::from_bits(quotient) * GTE_UNITS_PER_WORLD_UNIT as u32 / h.max(1) as u32
+        );
+        let screen_min = FractionalType::from_num(gte::SCREEN_MIN);
+        let screen_max = FractionalType::from_num(gte::SCREEN_MAX);
+        Vec2::new(
+            (homogeneous_coordinates.x * reciprocal).clamp(screen_min, screen_max),
+            (homogeneous_coordinates.y * reciprocal).clamp(screen_min, screen_max)
+        )
+    }
+}
+
+// World units to GTE units, rounded. Scaled in 64 bits, where a 16 bit
+// integer part would overflow past 128 world units.
+fn to_gte_units(value: FractionalType) -> i64
+{
+    (value.to_num::<fixed::types::I32F32>() * GTE_UNITS_PER_WORLD_UNIT as i64).round().to_num()
+}
+
+// a V register component, saturating past 128 world units
+fn to_gte_vector(value: FractionalType) -> i16
+{
+    to_gte_units(value).clamp(i16::MIN as i64, i16::MAX as i64) as i16
+}
+
+fn from_gte_units(value: i32) -> FractionalType
+{
+    FractionalType::from_num(fixed::types::I32F32::from_num(value) / GTE_UNITS_PER_WORLD_UNIT as i64)
+}
+
+fn to_proper_inf(num: FractionalType) -> FractionalType
+{
+    if num >= FractionalType::zero()
+    {
+        // TODO: had to change this to 10000 and -10000 or else sdl2 would
+        // allocate a 2^31^2 frame buffer and run out of memory
+        FractionalType::from_num(10000)//i32::MAX
+    }
+    else
+    {
+        FractionalType::from_num(-10000)//i32::MIN
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn vec3(x: i32, y: i32, z: i32) -> Vec3
+    {
+        Vec3::new(FractionalType::from_num(x), FractionalType::from_num(y), FractionalType::from_num(z))
+    }
+
+    fn pixel(point: Vec2) -> (i32, i32)
+    {
+        (point.x.round().to_num(), point.y.round().to_num())
+    }
+
+    #[test]
+    fn gte_saturates_far_vertices()
+    {
+        let mut camera = Camera::new(Vec3::zero(), (320, 240));
+        camera.use_gte = true;
+        // V saturates at 128 world units, on every axis alike so the
+        // direction is kept (and H / SZ3 lands a hair under one)
+        assert_eq!(pixel(camera.project(&vec3(500, 0, 500))), (319, 120));
+        assert_eq!(pixel(camera.project(&vec3(0, 0, 500))), (160, 120));
+    }
+
+    #[test]
+    fn gte_saturates_far_cameras()
+    {
+        // the translation fits, SZ3 saturates at 0xFFFF, i.e. 256 world units
+        let mut camera = Camera::new(vec3(0, 0, -500), (320, 240));
+        camera.use_gte = true;
+        assert_eq!(pixel(camera.project(&vec3(10, 10, 0))), (166, 113));
+    }
+
+    #[test]
+    fn hardware_divide_saturates_far_depths()
+    {
+        // small enough that the homogeneous coordinates fit at this depth
+        let mut camera = Camera::new(Vec3::zero(), (64, 48));
+        camera.divide_mode = DivideMode::Hardware;
+        // SZ3 saturates at 0xFFFF, the vertex is divided as if 256 units away
+        let projected = camera.project(&vec3(0, 0, 1000));
+        assert_eq!(projected, Vec2::new(FractionalType::from_num(125), FractionalType::from_num(93.75)));
+        // and close by it agrees with the exact divide
+        assert_eq!(pixel(camera.project(&vec3(0, 0, 100))), (32, 24));
+    }
+
+    #[test]
+    fn orientation_stays_orthonormal()
+    {
+        let mut camera = Camera::new(Vec3::zero(), (320, 240));
+        for step in 0..1000
+        {
+            camera.rotate_yaw(37);
+            camera.rotate_pitch(if step % 3 == 0 { -91 } else { 53 });
+            camera.rotate_roll(11);
+        }
+        let rows = camera.rotation_matrix().rows;
+        let epsilon = 1.0 / 256.0;
+        for (i, row) in rows.iter().enumerate()
+        {
+            for (j, other) in rows.iter().enumerate()
+            {
+                let dot: f64 = row.dot(*other).to_num();
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((dot - expected).abs() < epsilon, "rows {} and {}: {}", i, j, dot);
+            }
+        }
+        // right handed, no mirroring crept in
+        let determinant: f64 = camera.rotation_matrix().determinant().to_num();
+        assert!((determinant - 1.0).abs() < epsilon);
+        // and the forward vector is the third row
+        let forward = camera.forward();
+        let along: f64 = forward.dot(rows[2]).to_num();
+        assert!((along - 1.0).abs() < epsilon);
+    }
+
+    // the GTE floors to whole pixels and its divide can land a little low, so
+    // it may be off by a pixel and a bit
+    fn assert_paths_agree(camera: &mut Camera, points: &[Vec3])
+    {
+        for point in points
+        {
+            camera.use_gte = false;
+            let matrix = camera.project(point);
+            camera.use_gte = true;
+            let gte = camera.project(point);
+            let error: f64 = (matrix.x - gte.x).abs().max((matrix.y - gte.y).abs()).to_num();
+            assert!(error < 1.5, "{:?}: matrix {:?}, GTE {:?}", point, matrix, gte);
+        }
+    }
+
+    const SAMPLE_POINTS: [(i32, i32, i32); 5] = [(0, 0, 0), (1, 1, 1), (-2, 1, 3), (3, -2, -1), (0, 4, 2)];
+
+    #[test]
+    fn gte_and_matrix_agree()
+    {
+        let points = SAMPLE_POINTS.map(|(x, y, z)| vec3(x, y, z));
+        for resolution in [(320, 240), (256, 240), (640, 480)]
+        {
+            let mut camera = Camera::looking_at(vec3(3, 4, -12), Vec3::zero(), resolution);
+            camera.rotate_roll(100);
+            assert_paths_agree(&mut camera, &points);
+        }
+        // far enough that the camera translation needs more than a 16 bit
+        // integer part in GTE units
+        let mut camera = Camera::looking_at(vec3(20, -10, -150), Vec3::zero(), (320, 240));
+        assert_paths_agree(&mut camera, &points);
+    }
+
+    #[test]
+    fn gte_and_matrix_agree_orthographic()
+    {
+        let points = SAMPLE_POINTS.map(|(x, y, z)| vec3(x, y, z));
+        for resolution in [(320, 240), (368, 240)]
+        {
+            let mut camera = Camera::looking_at(vec3(-5, 2, -8), Vec3::zero(), resolution);
+            camera.set_projection_mode(ProjectionMode::Orthographic);
+            assert_paths_agree(&mut camera, &points);
+        }
+    }
+}
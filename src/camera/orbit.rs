@@ -0,0 +1,110 @@
+// Orbit (turntable) control: the camera circles a target point at some
+// distance, always facing it. Yaw turns about the world up axis and pitch
+// stops short of the poles, so the model never flips over.
+
+use super::Camera;
+use crate::fractional_type::{self, FractionalType};
+use crate::math::{Quat, Vec3};
+use num_traits::identities::{One, Zero};
+
+// the camera won't pitch closer than this to straight up or down
+const PITCH_LIMIT: i32 = fractional_type::ANGLE_QUARTER_TURN - 16;
+// the camera won't get closer to the target than this, in world units
+const MIN_DISTANCE: f32 = 0.0625;
+// nor farther than this, what a 16 bit integer part can project: at 640
+// pixels wide the camera matrix scales depth by 320, and the GTE's SZ3
+// saturates at 256 world units
+const MAX_DISTANCE: f32 = 64.0;
+// auto-rotate turns by this every update, a full turn in about 8.5 seconds at
+// 60 updates per second
+pub const DEFAULT_AUTO_ROTATE_STEP: i32 = 8;
+
+pub struct Orbit
+{
+    pub target: Vec3,
+    distance: FractionalType,
+    // in fractional_type::ANGLE_ONE_TURN units, same conventions as
+    // Camera::set_rotation
+    yaw: i32,
+    pitch: i32,
+    pub auto_rotate: bool,
+    pub auto_rotate_step: i32,
+}
+
+impl Orbit
+{
+    pub fn new(target: Vec3, distance: FractionalType, yaw: i32, pitch: i32) -> Self
+    {
+        let mut orbit = Self {
+            target,
+            distance: FractionalType::one(),
+            yaw: 0,
+            pitch: 0,
+            auto_rotate: false,
+            auto_rotate_step: DEFAULT_AUTO_ROTATE_STEP,
+        };
+        orbit.set_distance(distance);
+        orbit.orbit(yaw, pitch);
+        orbit
+    }
+
+    /// An orbit around `target` that starts where `camera` currently is, so
+    /// switching to it doesn't make the view jump more than needed to face
+    /// the target.
+    pub fn from_camera(camera: &Camera, target: Vec3) -> Self
+    {
+        let direction = target - camera.position();
+        let yaw = fractional_type::atan2(-direction.x, direction.z);
+        let horizontal = Vec3::new(direction.x, FractionalType::zero(), direction.z).length();
+        let pitch = fractional_type::atan2(direction.y, horizontal);
+        Self::new(target, direction.length(), yaw, pitch)
+    }
+
+    pub fn distance(&self) -> FractionalType
+    {
+        self.distance
+    }
+
+    pub fn set_distance(&mut self, distance: FractionalType)
+    {
+        self.distance = distance.clamp(FractionalType::from_num(MIN_DISTANCE), FractionalType::from_num(MAX_DISTANCE));
+    }
+
+    /// Circles around the target, a positive yaw moves the camera to the
+    /// right and a positive pitch moves it down so it looks up at the target.
+    pub fn orbit(&mut self, yaw: i32, pitch: i32)
+    {
+        self.yaw = (self.yaw + yaw).rem_euclid(fractional_type::ANGLE_ONE_TURN);
+        self.pitch = (self.pitch + pitch).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+
+    /// Moves towards the target for positive steps and away for negative ones,
+    /// each step by an eighth of the distance.
+    pub fn zoom(&mut self, steps: i32)
+    {
+        let (numerator, denominator) = if steps > 0 { (7, 8) } else { (8, 7) };
+        for _ in 0..steps.abs()
+        {
+            // clamped every step so the next one can't overflow
+            self.set_distance(self.distance * FractionalType::from_num(numerator) / FractionalType::from_num(denominator));
+        }
+    }
+
+    /// Advances the auto-rotation, call once per update.
+    pub fn update(&mut self)
+    {
+        if self.auto_rotate
+        {
+            self.orbit(self.auto_rotate_step, 0);
+        }
+    }
+
+    /// Places `camera` on the orbit, facing the target.
+    pub fn apply(&self, camera: &mut Camera)
+    {
+        let orientation = Quat::rotation_y(-self.yaw) * Quat::rotation_x(-self.pitch);
+        let forward = orientation.rotate(Vec3::new(FractionalType::zero(), FractionalType::zero(), FractionalType::one()));
+        camera.set_orientation(orientation);
+        camera.set_position(self.target - forward * self.distance);
+    }
+}
@@ -1,8 +1,282 @@
-use fixed::traits::FromFixed;
-use fixed::traits::ToFixed;
-use fixed::types::I16F16 as InternalType;
+use az::Cast;
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg(feature = "overflow-diagnostics")]
+pub mod diagnostics;
+
+// The representation behind FractionalType is picked with a cargo feature so
+// the whole pipeline can be switched at once:
+//   fixed-i16f16 - the default
+//   fixed-i20f12 - the precision of the GTE's 1.x.12 registers
+//   fixed-i32f32 - a high precision fixed point reference
+//   float-f32    - a floating point ground truth
+#[cfg(feature = "fixed-i16f16")]
+pub type InternalType = fixed::types::I16F16;
+#[cfg(feature = "fixed-i20f12")]
+pub type InternalType = fixed::types::I20F12;
+#[cfg(feature = "fixed-i32f32")]
+pub type InternalType = fixed::types::I32F32;
+#[cfg(feature = "float-f32")]
+pub type InternalType = f32;
+
+#[cfg(not(any(feature = "fixed-i16f16", feature = "fixed-i20f12", feature = "fixed-i32f32", feature = "float-f32")))]
+compile_error!("select a FractionalType representation: fixed-i16f16, fixed-i20f12, fixed-i32f32 or float-f32");
+#[cfg(any(
+    all(feature = "fixed-i16f16", any(feature = "fixed-i20f12", feature = "fixed-i32f32", feature = "float-f32")),
+    all(feature = "fixed-i20f12", any(feature = "fixed-i32f32", feature = "float-f32")),
+    all(feature = "fixed-i32f32", feature = "float-f32")
+))]
+compile_error!("only one FractionalType representation can be selected, use --no-default-features");
+
+/// The operations the renderer needs from a FractionalType representation.
+pub trait Backend:
+    Copy +
+    PartialEq +
+    PartialOrd +
+    std::fmt::Debug +
+    std::fmt::Display +
+    std::ops::Add<Output = Self> +
+    std::ops::Sub<Output = Self> +
+    std::ops::Mul<Output = Self> +
+    std::ops::Div<Output = Self> +
+    std::ops::Rem<Output = Self> +
+    std::ops::Neg<Output = Self>
+{
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn min_value() -> Self;
+    fn max_value() -> Self;
+    fn from_str_radix(src: &str, radix: u32) -> Option<Self>;
+    fn abs(self) -> Self;
+    fn signum(self) -> Self;
+    // None on overflow or division by zero
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn checked_div(self, other: Self) -> Option<Self>;
+    fn checked_rem(self, other: Self) -> Option<Self>;
+    fn checked_neg(self) -> Option<Self>;
+    fn round(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    /// Zero for negative values.
+    fn sqrt(self) -> Self;
+    /// A total order, so FractionalType can be Ord even when backed by a float.
+    fn total_cmp(&self, other: &Self) -> std::cmp::Ordering;
+}
+
+macro_rules! impl_fixed_backend
+{
+    ($($fixed:ty),*) =>
+    {
+        $(
+            impl Backend for $fixed
+            {
+                fn zero() -> Self
+                {
+                    Self::ZERO
+                }
+
+                fn one() -> Self
+                {
+                    Self::from_num(1)
+                }
+
+                fn min_value() -> Self
+                {
+                    Self::MIN
+                }
+
+                fn max_value() -> Self
+                {
+                    Self::MAX
+                }
+
+                fn from_str_radix(src: &str, radix: u32) -> Option<Self>
+                {
+                    match radix
+                    {
+                        2 => Self::from_str_binary(src).ok(),
+                        8 => Self::from_str_octal(src).ok(),
+                        10 => src.parse().ok(),
+                        16 => Self::from_str_hex(src).ok(),
+                        _ => None,
+                    }
+                }
+
+                fn abs(self) -> Self
+                {
+                    self.abs()
+                }
+
+                fn signum(self) -> Self
+                {
+                    self.signum()
+                }
+
+                fn checked_add(self, other: Self) -> Option<Self>
+                {
+                    self.checked_add(other)
+                }
+
+                fn checked_sub(self, other: Self) -> Option<Self>
+                {
+                    self.checked_sub(other)
+                }
+
+                fn checked_mul(self, other: Self) -> Option<Self>
+                {
+                    self.checked_mul(other)
+                }
+
+                fn checked_div(self, other: Self) -> Option<Self>
+                {
+                    self.checked_div(other)
+                }
+
+                fn checked_rem(self, other: Self) -> Option<Self>
+                {
+                    self.checked_rem(other)
+                }
+
+                fn checked_neg(self) -> Option<Self>
+                {
+                    self.checked_neg()
+                }
+
+                fn round(self) -> Self
+                {
+                    self.round()
+                }
+
+                fn floor(self) -> Self
+                {
+                    self.floor()
+                }
+
+                fn ceil(self) -> Self
+                {
+                    self.ceil()
+                }
+
+                // on the integer bits, every representation fits in I32F32
+                fn sqrt(self) -> Self
+                {
+                    let bits = fixed::types::I32F32::from_num(self).to_bits();
+                    if bits <= 0
+                    {
+                        return Self::ZERO;
+                    }
+                    // sqrt of a Q32 value is sqrt(bits << 32) in Q32
+                    let root = ((bits as u128) << 32).isqrt() as u64;
+                    Self::from_num(fixed::types::U32F32::from_bits(root))
+                }
+
+                fn total_cmp(&self, other: &Self) -> std::cmp::Ordering
+                {
+                    self.cmp(other)
+                }
+            }
+        )*
+    }
+}
+
+impl_fixed_backend!(fixed::types::I16F16, fixed::types::I20F12, fixed::types::I32F32);
+
+impl Backend for f32
+{
+    fn zero() -> Self
+    {
+        0.0
+    }
+
+    fn one() -> Self
+    {
+        1.0
+    }
+
+    fn min_value() -> Self
+    {
+        f32::MIN
+    }
+
+    fn max_value() -> Self
+    {
+        f32::MAX
+    }
+
+    fn from_str_radix(src: &str, radix: u32) -> Option<Self>
+    {
+        <f32 as num_traits::Num>::from_str_radix(src, radix).ok()
+    }
+
+    fn abs(self) -> Self
+    {
+        self.abs()
+    }
+
+    fn signum(self) -> Self
+    {
+        self.signum()
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self>
+    {
+        Some(self + other).filter(|result| result.is_finite())
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self>
+    {
+        Some(self - other).filter(|result| result.is_finite())
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self>
+    {
+        Some(self * other).filter(|result| result.is_finite())
+    }
+
+    fn checked_div(self, other: Self) -> Option<Self>
+    {
+        Some(self / other).filter(|result| result.is_finite())
+    }
+
+    fn checked_rem(self, other: Self) -> Option<Self>
+    {
+        Some(self % other).filter(|result| result.is_finite())
+    }
+
+    fn checked_neg(self) -> Option<Self>
+    {
+        Some(-self)
+    }
+
+    fn round(self) -> Self
+    {
+        self.round()
+    }
+
+    fn floor(self) -> Self
+    {
+        self.floor()
+    }
+
+    fn ceil(self) -> Self
+    {
+        self.ceil()
+    }
+
+    fn sqrt(self) -> Self
+    {
+        self.max(0.0).sqrt()
+    }
+
+    // adding zero turns -0.0 into 0.0, which total_cmp would order first
+    fn total_cmp(&self, other: &Self) -> std::cmp::Ordering
+    {
+        f32::total_cmp(&(self + 0.0), &(other + 0.0))
+    }
+}
+
+#[derive(Copy, Clone)]
 pub struct FractionalType(InternalType);
 
 impl FractionalType
@@ -12,14 +286,32 @@ impl FractionalType
         Self(fixed)
     }
 
-    pub fn from_num<T: ToFixed>(num: T) -> Self
+    #[cfg(not(feature = "overflow-diagnostics"))]
+    pub fn from_num<T: Cast<InternalType>>(num: T) -> Self
+    {
+        Self(num.cast())
+    }
+
+    #[cfg(feature = "overflow-diagnostics")]
+    #[track_caller]
+    pub fn from_num<T: Copy + az::CheckedCast<InternalType> + Cast<f64>>(num: T) -> Self
     {
-        Self(InternalType::from_num(num))
+        Self(diagnostics::convert(num))
     }
 
-    pub fn to_num<T: FromFixed>(&self) -> T
+    #[cfg(not(feature = "overflow-diagnostics"))]
+    pub fn to_num<T>(&self) -> T
+        where InternalType: Cast<T>
     {
-        self.0.to_num()
+        self.0.cast()
+    }
+
+    #[cfg(feature = "overflow-diagnostics")]
+    #[track_caller]
+    pub fn to_num<T>(&self) -> T
+        where InternalType: az::CheckedCast<T> + az::SaturatingCast<T>
+    {
+        diagnostics::convert_to(self.0)
     }
 
     pub fn round(&self) -> Self
@@ -36,45 +328,364 @@ impl FractionalType
     {
         Self(self.0.ceil())
     }
+
+    pub fn abs(&self) -> Self
+    {
+        Self(self.0.abs())
+    }
+
+    /// Square root, computed on the integer bits with the fixed point
+    /// representations. Negative values give zero.
+    pub fn sqrt(&self) -> Self
+    {
+        Self(Backend::sqrt(self.0))
+    }
+
+    /// A value in the 1.3.12 format used by the GTE and libgte, where 4096
+    /// is one.
+    pub fn from_q12(raw: i32) -> Self
+    {
+        Self::from_num(fixed::types::I20F12::from_bits(raw))
+    }
 }
 
-impl std::ops::Add for FractionalType
+///////////////////////////////
+//  FIXED POINT TRIGONOMETRY //
+///////////////////////////////
+
+// Angles are integers where a full turn is ANGLE_ONE_TURN, like libgte's
+// rsin/rcos, so the renderer never needs a floating point unit.
+pub const ANGLE_ONE_TURN: i32 = 4096;
+pub const ANGLE_QUARTER_TURN: i32 = ANGLE_ONE_TURN / 4;
+
+// sin over the first quarter turn in 1.3.12, built at compile time from a
+// Taylor series in 32 bit fixed point
+const SIN_TABLE: [i16; ANGLE_QUARTER_TURN as usize + 1] = build_sin_table();
+
+const fn build_sin_table() -> [i16; ANGLE_QUARTER_TURN as usize + 1]
 {
-    type Output = Self;
+    // pi in Q32
+    const PI: i128 = 13_493_037_705;
+    let mut table = [0i16; ANGLE_QUARTER_TURN as usize + 1];
+    let mut i = 0;
+    while i < table.len()
+    {
+        let x = i as i128 * PI / (ANGLE_ONE_TURN as i128 / 2);
+        let x_squared = (x * x) >> 32;
+        let mut term = x;
+        let mut sum = x;
+        let mut n = 1;
+        while n < 10
+        {
+            term = -((term * x_squared) >> 32) / ((2 * n) * (2 * n + 1));
+            sum += term;
+            n += 1;
+        }
+        table[i] = ((sum * 4096 + (1 << 31)) >> 32) as i16;
+        i += 1;
+    }
+    table
+}
+
+// atan(2^-i) in 1/65536ths of an angle unit, for the CORDIC in atan2
+const ATAN_TABLE: [i64; 20] = [
+    33554432, 19808338, 10466182, 5312797, 2666708, 1334654, 667490, 333765, 166885, 83443,
+    41722, 20861, 10430, 5215, 2608, 1304, 652, 326, 163, 81
+];
 
-    fn add(self, other: Self) -> Self
+/// sin of an angle in ANGLE_ONE_TURN units, in 1.3.12.
+pub fn rsin(angle: i32) -> i32
+{
+    let angle = angle.rem_euclid(ANGLE_ONE_TURN);
+    let quadrant = angle / ANGLE_QUARTER_TURN;
+    let offset = angle % ANGLE_QUARTER_TURN;
+    match quadrant
     {
-        Self(self.0 + other.0)
+        0 => SIN_TABLE[offset as usize] as i32,
+        1 => SIN_TABLE[(ANGLE_QUARTER_TURN - offset) as usize] as i32,
+        2 => -(SIN_TABLE[offset as usize] as i32),
+        _ => -(SIN_TABLE[(ANGLE_QUARTER_TURN - offset) as usize] as i32),
     }
 }
 
-impl std::ops::Sub for FractionalType
+/// cos of an angle in ANGLE_ONE_TURN units, in 1.3.12.
+pub fn rcos(angle: i32) -> i32
 {
-    type Output = Self;
+    rsin(angle.wrapping_add(ANGLE_QUARTER_TURN))
+}
+
+pub fn sin(angle: i32) -> FractionalType
+{
+    FractionalType::from_q12(rsin(angle))
+}
+
+pub fn cos(angle: i32) -> FractionalType
+{
+    FractionalType::from_q12(rcos(angle))
+}
 
-    fn sub(self, other: Self) -> Self
+/// sin of an angle in ANGLE_ONE_TURN units that has a fractional part,
+/// interpolated linearly between the table entries. Used where whole angle
+/// units are too coarse, such as slerp between nearby rotations.
+pub fn sin_fine(angle: FractionalType) -> FractionalType
+{
+    let whole = angle.floor();
+    let index: i32 = whole.to_num();
+    let (a, b) = (sin(index), sin(index + 1));
+    a + (b - a) * (angle - whole)
+}
+
+pub fn cos_fine(angle: FractionalType) -> FractionalType
+{
+    sin_fine(angle + FractionalType::from_num(ANGLE_QUARTER_TURN))
+}
+
+/// The angle of (x, y) in ANGLE_ONE_TURN units, in -ANGLE_ONE_TURN / 2 to
+/// ANGLE_ONE_TURN / 2, computed with CORDIC vectoring.
+pub fn atan2(y: FractionalType, x: FractionalType) -> i32
+{
+    ((cordic_atan2(y, x) + (1 << 15)) >> 16) as i32
+}
+
+/// atan2 keeping the fractional part of the angle.
+pub fn atan2_fine(y: FractionalType, x: FractionalType) -> FractionalType
+{
+    FractionalType::from_num(fixed::types::I48F16::from_bits(cordic_atan2(y, x)))
+}
+
+// the angle of (x, y) in 1/65536ths of an angle unit
+fn cordic_atan2(y: FractionalType, x: FractionalType) -> i64
+{
+    let mut x = x.to_num::<fixed::types::I32F32>().to_bits();
+    let mut y = y.to_num::<fixed::types::I32F32>().to_bits();
+    if x == 0 && y == 0
+    {
+        return 0;
+    }
+    // leave headroom for the CORDIC gain
+    while x.unsigned_abs() >= 1 << 60 || y.unsigned_abs() >= 1 << 60
     {
-        Self(self.0 - other.0)
+        x >>= 1;
+        y >>= 1;
     }
+    // rotate into the right half plane first
+    let mut angle: i64 = 0;
+    if x < 0
+    {
+        let half_turn = (ANGLE_ONE_TURN as i64 / 2) << 16;
+        (x, y, angle) = if y >= 0 { (-x, -y, half_turn) } else { (-x, -y, -half_turn) };
+    }
+    for (i, step) in ATAN_TABLE.iter().enumerate()
+    {
+        let (x_shifted, y_shifted) = (x >> i, y >> i);
+        if y > 0
+        {
+            (x, y) = (x + y_shifted, y - x_shifted);
+            angle += step;
+        }
+        else
+        {
+            (x, y) = (x - y_shifted, y + x_shifted);
+            angle -= step;
+        }
+    }
+    angle
 }
 
-impl std::ops::Mul for FractionalType
+impl Default for FractionalType
 {
-    type Output = Self;
+    fn default() -> Self
+    {
+        num_traits::identities::Zero::zero()
+    }
+}
+
+impl std::fmt::Debug for FractionalType
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        std::fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl std::fmt::Display for FractionalType
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// plain == so -0.0 equals 0.0 with the float backend, whose checked
+// arithmetic never lets a NaN through that would make this not reflexive
+impl PartialEq for FractionalType
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.0 == other.0
+    }
+}
+
+impl Eq for FractionalType {}
 
-    fn mul(self, other: Self) -> Self
+impl PartialOrd for FractionalType
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering>
+    {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FractionalType
+{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering
+    {
+        Backend::total_cmp(&self.0, &other.0)
+    }
+}
+
+macro_rules! impl_binary_operator
+{
+    ($($trait:ident $method:ident $checked_method:ident $assign_trait:ident $assign_method:ident),*) =>
     {
-        Self(self.0 * other.0)
+        $(
+            impl std::ops::$trait for FractionalType
+            {
+                type Output = Self;
+
+                #[cfg(not(feature = "overflow-diagnostics"))]
+                fn $method(self, other: Self) -> Self
+                {
+                    Self(self.0.$method(other.0))
+                }
+
+                #[cfg(feature = "overflow-diagnostics")]
+                #[track_caller]
+                fn $method(self, other: Self) -> Self
+                {
+                    Self(diagnostics::binary(
+                        stringify!($method),
+                        self.0,
+                        other.0,
+                        <InternalType as Backend>::$checked_method,
+                        <f64 as std::ops::$trait>::$method))
+                }
+            }
+
+            impl std::ops::$trait<&FractionalType> for FractionalType
+            {
+                type Output = Self;
+
+                #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+                fn $method(self, other: &Self) -> Self
+                {
+                    std::ops::$trait::$method(self, *other)
+                }
+            }
+
+            impl std::ops::$assign_trait for FractionalType
+            {
+                #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+                fn $assign_method(&mut self, other: Self)
+                {
+                    *self = std::ops::$trait::$method(*self, other);
+                }
+            }
+
+            impl std::ops::$assign_trait<&FractionalType> for FractionalType
+            {
+                #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+                fn $assign_method(&mut self, other: &Self)
+                {
+                    *self = std::ops::$trait::$method(*self, *other);
+                }
+            }
+        )*
     }
 }
 
-impl std::ops::Div for FractionalType
+impl_binary_operator!(
+    Add add checked_add AddAssign add_assign,
+    Sub sub checked_sub SubAssign sub_assign,
+    Mul mul checked_mul MulAssign mul_assign,
+    Div div checked_div DivAssign div_assign,
+    Rem rem checked_rem RemAssign rem_assign
+);
+
+impl std::ops::Neg for FractionalType
 {
     type Output = Self;
 
-    fn div(self, other: Self) -> Self
+    #[cfg(not(feature = "overflow-diagnostics"))]
+    fn neg(self) -> Self
+    {
+        Self(-self.0)
+    }
+
+    #[cfg(feature = "overflow-diagnostics")]
+    #[track_caller]
+    fn neg(self) -> Self
+    {
+        Self(diagnostics::negate(self.0))
+    }
+}
+
+// loops rather than folds, a closure would hide the caller from the
+// operators' #[track_caller] (Iterator::sum and product still report their
+// own location, call these directly to get the caller's)
+impl std::iter::Sum for FractionalType
+{
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self
+    {
+        let mut sum = num_traits::identities::Zero::zero();
+        for x in iter
+        {
+            sum += x;
+        }
+        sum
+    }
+}
+
+impl<'a> std::iter::Sum<&'a FractionalType> for FractionalType
+{
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self
+    {
+        let mut sum = num_traits::identities::Zero::zero();
+        for &x in iter
+        {
+            sum += x;
+        }
+        sum
+    }
+}
+
+impl std::iter::Product for FractionalType
+{
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self
     {
-        Self(self.0 / other.0)
+        let mut product = num_traits::identities::One::one();
+        for x in iter
+        {
+            product *= x;
+        }
+        product
+    }
+}
+
+impl<'a> std::iter::Product<&'a FractionalType> for FractionalType
+{
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn product<I: Iterator<Item = &'a Self>>(iter: I) -> Self
+    {
+        let mut product = num_traits::identities::One::one();
+        for &x in iter
+        {
+            product *= x;
+        }
+        product
     }
 }
 
@@ -83,7 +694,7 @@ impl num_traits::identities::Zero for FractionalType
 
     fn zero() -> Self
     {
-        Self(InternalType::from_num(0))
+        Self(<InternalType as Backend>::zero())
     }
 
     fn is_zero(&self) -> bool
@@ -91,4 +702,264 @@ impl num_traits::identities::Zero for FractionalType
         let z = Self::zero();
         self == &z
     }
-}
\ No newline at end of file
+}
+
+impl num_traits::identities::One for FractionalType
+{
+    fn one() -> Self
+    {
+        Self(<InternalType as Backend>::one())
+    }
+}
+
+impl num_traits::bounds::Bounded for FractionalType
+{
+    fn min_value() -> Self
+    {
+        Self(<InternalType as Backend>::min_value())
+    }
+
+    fn max_value() -> Self
+    {
+        Self(<InternalType as Backend>::max_value())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseFractionalTypeError;
+
+impl std::fmt::Display for ParseFractionalTypeError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.write_str("invalid FractionalType literal")
+    }
+}
+
+impl std::error::Error for ParseFractionalTypeError {}
+
+impl std::str::FromStr for FractionalType
+{
+    type Err = ParseFractionalTypeError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err>
+    {
+        <Self as num_traits::Num>::from_str_radix(src, 10)
+    }
+}
+
+impl num_traits::Num for FractionalType
+{
+    type FromStrRadixErr = ParseFractionalTypeError;
+
+    fn from_str_radix(src: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr>
+    {
+        <InternalType as Backend>::from_str_radix(src, radix)
+            .map(Self)
+            .ok_or(ParseFractionalTypeError)
+    }
+}
+
+impl num_traits::sign::Signed for FractionalType
+{
+    fn abs(&self) -> Self
+    {
+        Self(self.0.abs())
+    }
+
+    fn abs_sub(&self, other: &Self) -> Self
+    {
+        if self <= other
+        {
+            num_traits::identities::Zero::zero()
+        }
+        else
+        {
+            *self - *other
+        }
+    }
+
+    fn signum(&self) -> Self
+    {
+        Self(self.0.signum())
+    }
+
+    fn is_positive(&self) -> bool
+    {
+        self.0 > <InternalType as Backend>::zero()
+    }
+
+    fn is_negative(&self) -> bool
+    {
+        self.0 < <InternalType as Backend>::zero()
+    }
+}
+
+impl num_traits::cast::ToPrimitive for FractionalType
+{
+    fn to_i64(&self) -> Option<i64>
+    {
+        az::CheckedCast::checked_cast(self.0)
+    }
+
+    fn to_u64(&self) -> Option<u64>
+    {
+        az::CheckedCast::checked_cast(self.0)
+    }
+
+    fn to_f32(&self) -> Option<f32>
+    {
+        Some(self.0.cast())
+    }
+
+    fn to_f64(&self) -> Option<f64>
+    {
+        Some(self.0.cast())
+    }
+}
+
+impl num_traits::cast::FromPrimitive for FractionalType
+{
+    fn from_i64(n: i64) -> Option<Self>
+    {
+        az::CheckedCast::checked_cast(n).map(Self)
+    }
+
+    fn from_u64(n: u64) -> Option<Self>
+    {
+        az::CheckedCast::checked_cast(n).map(Self)
+    }
+
+    fn from_f32(n: f32) -> Option<Self>
+    {
+        az::CheckedCast::checked_cast(n).map(Self)
+    }
+
+    fn from_f64(n: f64) -> Option<Self>
+    {
+        az::CheckedCast::checked_cast(n).map(Self)
+    }
+}
+
+impl num_traits::cast::NumCast for FractionalType
+{
+    fn from<T: num_traits::cast::ToPrimitive>(n: T) -> Option<Self>
+    {
+        // integers go through i64 so that large values keep their precision
+        match n.to_i64()
+        {
+            Some(integer) if n.to_f64() == Some(integer as f64) => num_traits::cast::FromPrimitive::from_i64(integer),
+            _ => n.to_f64().and_then(num_traits::cast::FromPrimitive::from_f64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn turns_to_radians(angle: f64) -> f64
+    {
+        angle * std::f64::consts::TAU / ANGLE_ONE_TURN as f64
+    }
+
+    fn radians_to_turns(angle: f64) -> f64
+    {
+        angle * ANGLE_ONE_TURN as f64 / std::f64::consts::TAU
+    }
+
+    fn num(value: f64) -> FractionalType
+    {
+        FractionalType::from_num(value)
+    }
+
+    #[test]
+    fn rsin_and_rcos_match_f64()
+    {
+        for angle in -2 * ANGLE_ONE_TURN..=2 * ANGLE_ONE_TURN
+        {
+            let radians = turns_to_radians(angle as f64);
+            assert!((rsin(angle) as f64 - radians.sin() * 4096.0).abs() <= 1.0, "rsin({})", angle);
+            assert!((rcos(angle) as f64 - radians.cos() * 4096.0).abs() <= 1.0, "rcos({})", angle);
+        }
+    }
+
+    #[test]
+    fn rsin_and_rcos_are_exact_at_quadrant_boundaries()
+    {
+        let quarter = ANGLE_QUARTER_TURN;
+        assert_eq!([0, quarter, 2 * quarter, 3 * quarter, 4 * quarter].map(rsin), [0, 4096, 0, -4096, 0]);
+        assert_eq!([0, quarter, 2 * quarter, 3 * quarter, 4 * quarter].map(rcos), [4096, 0, -4096, 0, 4096]);
+        assert_eq!([-quarter, -2 * quarter, -3 * quarter].map(rsin), [-4096, 0, 4096]);
+        // a whole number of turns apart, wrapping around
+        assert_eq!(rcos(i32::MAX), rcos(ANGLE_ONE_TURN - 1));
+        assert_eq!(rsin(i32::MIN), 0);
+    }
+
+    #[test]
+    fn atan2_matches_f64()
+    {
+        for y in -8..=8
+        {
+            for x in -8..=8
+            {
+                if x == 0 && y == 0
+                {
+                    continue;
+                }
+                let (y, x) = (y as f64 * 0.75, x as f64 * 1.25);
+                let expected = radians_to_turns(y.atan2(x));
+                let fine: f64 = atan2_fine(num(y), num(x)).to_num();
+                assert!((fine - expected).abs() < 0.05, "atan2_fine({}, {}) = {}, not {}", y, x, fine, expected);
+                assert!((atan2(num(y), num(x)) as f64 - expected).abs() <= 0.55, "atan2({}, {})", y, x);
+            }
+        }
+    }
+
+    #[test]
+    fn atan2_on_the_axes()
+    {
+        let (zero, one) = (num(0.0), num(1.0));
+        let half_turn = ANGLE_ONE_TURN / 2;
+        assert_eq!(atan2(zero, one), 0);
+        assert_eq!(atan2(one, zero), ANGLE_QUARTER_TURN);
+        assert_eq!(atan2(zero, -one), half_turn);
+        assert_eq!(atan2(-one, zero), -ANGLE_QUARTER_TURN);
+        assert_eq!(atan2(-one, -one), -3 * ANGLE_ONE_TURN / 8);
+        assert_eq!(atan2(zero, zero), 0);
+        assert_eq!(atan2_fine(zero, zero), zero);
+    }
+
+    #[test]
+    fn atan2_of_large_and_small_vectors()
+    {
+        let expected = radians_to_turns(3.0f64.atan2(4.0));
+        for scale in [1.0 / 256.0, 1.0, 1000.0]
+        {
+            let angle: f64 = atan2_fine(num(3.0 * scale), num(4.0 * scale)).to_num();
+            assert!((angle - expected).abs() < 0.05, "scale {}", scale);
+        }
+    }
+
+    #[test]
+    fn sqrt_matches_f64()
+    {
+        for value in [0.25, 0.5, 2.0, 3.0, 100.0, 181.0, 12345.678]
+        {
+            let root: f64 = num(value).sqrt().to_num();
+            assert!((root - value.sqrt()).abs() < 1.0 / 1024.0, "sqrt({}) = {}", value, root);
+        }
+    }
+
+    #[test]
+    fn sqrt_edge_cases()
+    {
+        assert_eq!(num(0.0).sqrt(), num(0.0));
+        assert_eq!(num(-4.0).sqrt(), num(0.0));
+        let max = FractionalType::from_fixed(<InternalType as Backend>::max_value());
+        let max_f64: f64 = max.to_num();
+        let root: f64 = max.sqrt().to_num();
+        assert!((root - max_f64.sqrt()).abs() <= max_f64.sqrt() * 1e-6 + 1.0 / 1024.0, "sqrt(max) = {}", root);
+    }
+}
@@ -0,0 +1,156 @@
+// Overflow and precision diagnostics for FractionalType, enabled with the
+// `overflow-diagnostics` cargo feature.
+//
+// Every arithmetic operation and conversion is checked against an f64
+// reference. Problems are counted per call site (operators are
+// #[track_caller]) and print_report lists the worst offenders, so we can find
+// where the pipeline loses precision. Overflowing results saturate instead of
+// panicking or wrapping so a whole run can be profiled.
+
+use super::{Backend, InternalType};
+use az::Cast;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::panic::Location;
+
+// a result further than this (relative) from the exact value counts as a
+// precision loss
+const PRECISION_LOSS_THRESHOLD: f64 = 0.01;
+
+#[derive(Copy, Clone, Default)]
+struct Counts
+{
+    operations: u64,
+    overflows: u64,
+    // a non-zero exact value that rounded to zero
+    underflows: u64,
+    precision_losses: u64,
+}
+
+impl Counts
+{
+    fn problems(&self) -> u64
+    {
+        self.overflows + self.underflows + self.precision_losses
+    }
+}
+
+thread_local!
+{
+    static CALL_SITES: RefCell<BTreeMap<(Location<'static>, &'static str), Counts>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+#[track_caller]
+fn counts_at(operation: &'static str, update: impl FnOnce(&mut Counts))
+{
+    let location = *Location::caller();
+    CALL_SITES.with(|call_sites| {
+        let mut call_sites = call_sites.borrow_mut();
+        let counts = call_sites.entry((location, operation)).or_default();
+        counts.operations += 1;
+        update(counts);
+    });
+}
+
+#[track_caller]
+fn record(operation: &'static str, result: Option<InternalType>, exact: f64) -> InternalType
+{
+    let result = match result
+    {
+        Some(result) => result,
+        None if exact < 0.0 => InternalType::min_value(),
+        None => InternalType::max_value(),
+    };
+    let result_f64: f64 = result.cast();
+    let max: f64 = InternalType::max_value().cast();
+    let min: f64 = InternalType::min_value().cast();
+    counts_at(operation, |counts| {
+        if result_f64 != exact
+        {
+            if !exact.is_finite() || exact > max || exact < min
+            {
+                counts.overflows += 1;
+            }
+            else if result_f64 == 0.0
+            {
+                counts.underflows += 1;
+            }
+            else if ((result_f64 - exact) / exact).abs() > PRECISION_LOSS_THRESHOLD
+            {
+                counts.precision_losses += 1;
+            }
+        }
+    });
+    result
+}
+
+/// Applies a binary operation, counting overflow and precision loss against
+/// the exact result.
+#[track_caller]
+pub(super) fn binary(
+    operation: &'static str,
+    a: InternalType,
+    b: InternalType,
+    checked: fn(InternalType, InternalType) -> Option<InternalType>,
+    exact: fn(f64, f64) -> f64) -> InternalType
+{
+    record(operation, checked(a, b), exact(a.cast(), b.cast()))
+}
+
+#[track_caller]
+pub(super) fn negate(a: InternalType) -> InternalType
+{
+    let exact: f64 = a.cast();
+    record("neg", a.checked_neg(), -exact)
+}
+
+#[track_caller]
+pub(super) fn convert<T: Copy + az::CheckedCast<InternalType> + Cast<f64>>(num: T) -> InternalType
+{
+    let exact: f64 = num.cast();
+    record("from_num", num.checked_cast(), exact)
+}
+
+/// Converts out of InternalType, counting values the target can't hold,
+/// which saturate. Dropping fractional bits is what to_num is for, so that
+/// isn't counted as a precision loss.
+#[track_caller]
+pub(super) fn convert_to<T>(value: InternalType) -> T
+    where InternalType: az::CheckedCast<T> + az::SaturatingCast<T>
+{
+    let result = az::CheckedCast::checked_cast(value);
+    counts_at("to_num", |counts| {
+        if result.is_none()
+        {
+            counts.overflows += 1;
+        }
+    });
+    result.unwrap_or_else(|| az::SaturatingCast::saturating_cast(value))
+}
+
+/// Prints every call site that overflowed or lost precision, worst first.
+pub fn print_report()
+{
+    CALL_SITES.with(|call_sites| {
+        let call_sites = call_sites.borrow();
+        let mut problems: Vec<_> = call_sites.iter()
+            .filter(|(_, counts)| counts.problems() > 0)
+            .collect();
+        problems.sort_by_key(|(_, counts)| std::cmp::Reverse((counts.overflows, counts.problems())));
+
+        println!("FractionalType diagnostics ({} call sites checked, {} with problems)", call_sites.len(), problems.len());
+        println!("{:>12} {:>12} {:>12} {:>12}  {:<8} call site", "operations", "overflows", "underflows", "imprecise", "op");
+        for ((location, operation), counts) in problems
+        {
+            println!(
+                "{:>12} {:>12} {:>12} {:>12}  {:<8} {}",
+                counts.operations,
+                counts.overflows,
+                counts.underflows,
+                counts.precision_losses,
+                operation,
+                location
+            );
+        }
+    });
+}
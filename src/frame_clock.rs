@@ -0,0 +1,246 @@
+use crate::fractional_type::FractionalType;
+use std::time::{Duration, Instant};
+
+// Frame pacing with a fixed simulation timestep. Like on the PSX the
+// simulation ticks once per video field (60 Hz NTSC, 50 Hz PAL) no matter how
+// often we draw, so movement is the same at every frame rate target and on
+// every machine. Drawing is paced to the target by sleeping until the next
+// frame's deadline, which unlike a fixed sleep accounts for the time spent
+// rendering. With vsync presenting already waits for the display's refresh,
+// so waiting for our own deadline as well could miss a refresh and halve the
+// frame rate.
+
+// a frame longer than this many simulation steps (e.g. while the window is
+// dragged) is cut short instead of trying to catch up
+const MAX_STEPS_PER_FRAME: u32 = 8;
+// sleeping is only accurate to a millisecond or so, the rest is spun
+const SPIN_TIME: Duration = Duration::from_millis(1);
+// how many frames the statistics cover
+const STATS_WINDOW: usize = 120;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FrameRate
+{
+    Ntsc60,
+    Pal50,
+    Ntsc30,
+    Pal25,
+}
+
+impl FrameRate
+{
+    pub const ALL: [FrameRate; 4] = [FrameRate::Ntsc60, FrameRate::Pal50, FrameRate::Ntsc30, FrameRate::Pal25];
+
+    /// Frames drawn per second.
+    pub fn frames_per_second(self) -> u32
+    {
+        match self
+        {
+            FrameRate::Ntsc60 => 60,
+            FrameRate::Pal50 => 50,
+            FrameRate::Ntsc30 => 30,
+            FrameRate::Pal25 => 25,
+        }
+    }
+
+    /// Fields per second of the video standard, which is the simulation rate.
+    pub fn fields_per_second(self) -> u32
+    {
+        match self
+        {
+            FrameRate::Ntsc60 | FrameRate::Ntsc30 => 60,
+            FrameRate::Pal50 | FrameRate::Pal25 => 50,
+        }
+    }
+
+    pub fn frame_duration(self) -> Duration
+    {
+        Duration::from_secs(1) / self.frames_per_second()
+    }
+
+    pub fn step_duration(self) -> Duration
+    {
+        Duration::from_secs(1) / self.fields_per_second()
+    }
+
+    /// The next target, wrapping around, for cycling through them with a key.
+    pub fn next(self) -> Self
+    {
+        let index = Self::ALL.iter().position(|&rate| rate == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl std::fmt::Display for FrameRate
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        let standard = if self.fields_per_second() == 60 { "NTSC" } else { "PAL" };
+        write!(f, "{} fps ({})", self.frames_per_second(), standard)
+    }
+}
+
+/// Frame time statistics over the last STATS_WINDOW frames.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct FrameStats
+{
+    pub frames_per_second: f32,
+    /// full frame times including pacing, in milliseconds
+    pub average_ms: f32,
+    pub min_ms: f32,
+    pub max_ms: f32,
+    /// time spent on a frame before pacing, in milliseconds
+    pub busy_ms: f32,
+}
+
+impl std::fmt::Display for FrameStats
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(
+            f,
+            "{:.1} fps, frame {:.2} ms (min {:.2}, max {:.2}), busy {:.2} ms",
+            self.frames_per_second,
+            self.average_ms,
+            self.min_ms,
+            self.max_ms,
+            self.busy_ms
+        )
+    }
+}
+
+pub struct FrameClock
+{
+    rate: FrameRate,
+    // presenting waits for vblank, end_frame doesn't wait itself
+    vsync: bool,
+    last_frame: Instant,
+    next_deadline: Instant,
+    accumulator: Duration,
+    frame_times: Vec<Duration>,
+    busy_times: Vec<Duration>,
+}
+
+impl FrameClock
+{
+    pub fn new(rate: FrameRate, vsync: bool) -> Self
+    {
+        let now = Instant::now();
+        Self {
+            rate,
+            vsync,
+            last_frame: now,
+            next_deadline: now + rate.frame_duration(),
+            accumulator: Duration::ZERO,
+            frame_times: Vec::with_capacity(STATS_WINDOW),
+            busy_times: Vec::with_capacity(STATS_WINDOW),
+        }
+    }
+
+    pub fn rate(&self) -> FrameRate
+    {
+        self.rate
+    }
+
+    /// Whether presenting paces the frames instead of the target rate, which
+    /// then only sets the simulation rate.
+    pub fn vsync(&self) -> bool
+    {
+        self.vsync
+    }
+
+    pub fn set_rate(&mut self, rate: FrameRate)
+    {
+        self.rate = rate;
+        self.next_deadline = Instant::now() + rate.frame_duration();
+    }
+
+    /// The fixed simulation timestep, in seconds.
+    pub fn step(&self) -> FractionalType
+    {
+        FractionalType::from_num(1) / FractionalType::from_num(self.rate.fields_per_second())
+    }
+
+    /// Starts a frame and returns how many simulation steps to run to catch
+    /// up with real time.
+    pub fn begin_frame(&mut self) -> u32
+    {
+        let now = Instant::now();
+        let elapsed = now - self.last_frame;
+        self.last_frame = now;
+        record(&mut self.frame_times, elapsed);
+
+        let step = self.rate.step_duration();
+        self.accumulator += elapsed;
+        let steps = (self.accumulator.as_nanos() / step.as_nanos()) as u32;
+        self.accumulator -= step * steps;
+        if steps > MAX_STEPS_PER_FRAME
+        {
+            self.accumulator = Duration::ZERO;
+            MAX_STEPS_PER_FRAME
+        }
+        else
+        {
+            steps
+        }
+    }
+
+    /// Ends a frame, waiting until it's time for the next one unless vsync
+    /// paces presenting. Call right before presenting.
+    pub fn end_frame(&mut self)
+    {
+        let now = Instant::now();
+        record(&mut self.busy_times, now - self.last_frame);
+        if self.vsync
+        {
+            return;
+        }
+
+        let frame_duration = self.rate.frame_duration();
+        if now > self.next_deadline + frame_duration
+        {
+            // too far behind to catch up, start over instead of rushing frames
+            self.next_deadline = now + frame_duration;
+            return;
+        }
+        if let Some(remaining) = self.next_deadline.checked_duration_since(now)
+        {
+            if remaining > SPIN_TIME
+            {
+                std::thread::sleep(remaining - SPIN_TIME);
+            }
+            while Instant::now() < self.next_deadline
+            {
+                std::hint::spin_loop();
+            }
+        }
+        self.next_deadline += frame_duration;
+    }
+
+    pub fn stats(&self) -> FrameStats
+    {
+        if self.frame_times.is_empty()
+        {
+            return FrameStats::default();
+        }
+        let ms = |duration: &Duration| duration.as_secs_f32() * 1000.0;
+        let average = |times: &[Duration]| times.iter().map(ms).sum::<f32>() / times.len().max(1) as f32;
+        let average_ms = average(&self.frame_times);
+        FrameStats {
+            frames_per_second: if average_ms > 0.0 { 1000.0 / average_ms } else { 0.0 },
+            average_ms,
+            min_ms: self.frame_times.iter().map(ms).fold(f32::INFINITY, f32::min),
+            max_ms: self.frame_times.iter().map(ms).fold(0.0, f32::max),
+            busy_ms: average(&self.busy_times),
+        }
+    }
+}
+
+fn record(times: &mut Vec<Duration>, time: Duration)
+{
+    if times.len() == STATS_WINDOW
+    {
+        times.remove(0);
+    }
+    times.push(time);
+}
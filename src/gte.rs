@@ -0,0 +1,866 @@
+use crate::fractional_type::FractionalType;
+
+// Emulation of the PSX Geometry Transformation Engine (COP2).
+//
+// Register layout, saturation ranges and flag bits follow the psx-spx
+// documentation: https://psx-spx.consoledev.net/geometrytransformationenginegte/
+//
+// Fixed point formats used below:
+//   1.3.12  - i16 matrix elements (sign, 3 integer bits, 12 fraction bits)
+//   1.15.16 - i32 screen offsets OFX/OFY
+//   1.7.8   - i16 DQA
+//   1.7.24  - i32 DQB
+
+// FLAG register bits
+pub const FLAG_MAC1_POSITIVE_OVERFLOW: u32 = 1 << 30;
+pub const FLAG_MAC2_POSITIVE_OVERFLOW: u32 = 1 << 29;
+pub const FLAG_MAC3_POSITIVE_OVERFLOW: u32 = 1 << 28;
+pub const FLAG_MAC1_NEGATIVE_OVERFLOW: u32 = 1 << 27;
+pub const FLAG_MAC2_NEGATIVE_OVERFLOW: u32 = 1 << 26;
+pub const FLAG_MAC3_NEGATIVE_OVERFLOW: u32 = 1 << 25;
+pub const FLAG_IR1_SATURATED: u32 = 1 << 24;
+pub const FLAG_IR2_SATURATED: u32 = 1 << 23;
+pub const FLAG_IR3_SATURATED: u32 = 1 << 22;
+pub const FLAG_COLOR_R_SATURATED: u32 = 1 << 21;
+pub const FLAG_COLOR_G_SATURATED: u32 = 1 << 20;
+pub const FLAG_COLOR_B_SATURATED: u32 = 1 << 19;
+pub const FLAG_SZ3_OTZ_SATURATED: u32 = 1 << 18;
+pub const FLAG_DIVIDE_OVERFLOW: u32 = 1 << 17;
+pub const FLAG_MAC0_POSITIVE_OVERFLOW: u32 = 1 << 16;
+pub const FLAG_MAC0_NEGATIVE_OVERFLOW: u32 = 1 << 15;
+pub const FLAG_SX2_SATURATED: u32 = 1 << 14;
+pub const FLAG_SY2_SATURATED: u32 = 1 << 13;
+pub const FLAG_IR0_SATURATED: u32 = 1 << 12;
+pub const FLAG_ERROR: u32 = 1 << 31;
+// bits 30..23 and 18..13 are summarized in bit 31
+const FLAG_ERROR_MASK: u32 = 0x7F87_E000;
+
+const MAC_POSITIVE_OVERFLOW: [u32; 3] = [FLAG_MAC1_POSITIVE_OVERFLOW, FLAG_MAC2_POSITIVE_OVERFLOW, FLAG_MAC3_POSITIVE_OVERFLOW];
+const MAC_NEGATIVE_OVERFLOW: [u32; 3] = [FLAG_MAC1_NEGATIVE_OVERFLOW, FLAG_MAC2_NEGATIVE_OVERFLOW, FLAG_MAC3_NEGATIVE_OVERFLOW];
+const IR_SATURATED: [u32; 3] = [FLAG_IR1_SATURATED, FLAG_IR2_SATURATED, FLAG_IR3_SATURATED];
+const COLOR_SATURATED: [u32; 3] = [FLAG_COLOR_R_SATURATED, FLAG_COLOR_G_SATURATED, FLAG_COLOR_B_SATURATED];
+
+// the largest quotient the divider can produce, a bit under 2.0 in 1.16
+pub const DIVIDE_MAX: u32 = 0x1FFFF;
+// screen coordinates saturate to this range
+pub const SCREEN_MIN: i32 = -0x400;
+pub const SCREEN_MAX: i32 = 0x3FF;
+
+// Initial guesses for the Newton-Raphson reciprocal in `divide`, indexed by
+// the top bits of the normalized divisor.
+const UNR_TABLE: [u8; 0x101] = build_unr_table();
+
+const fn build_unr_table() -> [u8; 0x101]
+{
+    let mut table = [0u8; 0x101];
+    let mut i = 0;
+    while i < table.len()
+    {
+        let value = (0x40000 / (i as i32 + 0x100) + 1) / 2 - 0x101;
+        table[i] = if value > 0 { value as u8 } else { 0 };
+        i += 1;
+    }
+    table
+}
+
+/// H / SZ3 the way the GTE computes it: an unsigned Newton-Raphson
+/// reciprocal seeded from `UNR_TABLE`. The result is in 1.16 and saturates
+/// at `DIVIDE_MAX`; the second value is the overflow flag, set whenever the
+/// quotient would be 2.0 or more (including SZ3 = 0).
+pub fn divide(h: u16, sz3: u16) -> (u32, bool)
+{
+    let h = h as u64;
+    let sz3_wide = sz3 as u64;
+    if h >= sz3_wide * 2
+    {
+        return (DIVIDE_MAX, true);
+    }
+    let shift = sz3.leading_zeros();
+    let n = h << shift;
+    let d = sz3_wide << shift;
+    let u = UNR_TABLE[((d - 0x7FC0) >> 7) as usize] as u64 + 0x101;
+    let d = (0x200_0080 - d * u) >> 8;
+    let d = (0x80 + d * u) >> 8;
+    (DIVIDE_MAX.min(((n * d + 0x8000) >> 16) as u32), false)
+}
+
+/// Matrix operand of MVMVA (the `mx` field of the command).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MvmvaMatrix
+{
+    Rotation,
+    Light,
+    Color,
+    // mx=3 selects a garbage matrix built from RGBC, IR0 and the rotation matrix
+    Reserved,
+}
+
+/// Vector operand of MVMVA (the `v` field of the command).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MvmvaVector
+{
+    V0,
+    V1,
+    V2,
+    Ir,
+}
+
+/// Translation operand of MVMVA (the `cv` field of the command).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MvmvaTranslation
+{
+    Translation,
+    BackgroundColor,
+    // the hardware computes this one incorrectly, see `Gte::mvmva`
+    FarColor,
+    None,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Gte
+{
+    // control registers
+    /// rotation matrix RT, 1.3.12
+    pub rotation: [[i16; 3]; 3],
+    /// translation vector TRX, TRY, TRZ
+    pub translation: [i32; 3],
+    /// light source matrix LLM, 1.3.12
+    pub light: [[i16; 3]; 3],
+    /// background color RBK, GBK, BBK, 1.19.12
+    pub background_color: [i32; 3],
+    /// light color matrix LCM, 1.3.12
+    pub color: [[i16; 3]; 3],
+    /// far color RFC, GFC, BFC, 1.27.4
+    pub far_color: [i32; 3],
+    /// screen offset, 1.15.16
+    pub ofx: i32,
+    pub ofy: i32,
+    /// projection plane distance
+    pub h: u16,
+    /// depth queing coefficient, 1.7.8
+    pub dqa: i16,
+    /// depth queing offset, 1.7.24
+    pub dqb: i32,
+    /// average Z scale factors, 1.3.12
+    pub zsf3: i16,
+    pub zsf4: i16,
+    pub flag: u32,
+
+    // data registers
+    /// input vectors V0, V1, V2
+    pub v: [[i16; 3]; 3],
+    /// color and GPU command code
+    pub rgbc: [u8; 4],
+    /// ordering table Z
+    pub otz: u16,
+    /// intermediate registers IR0..IR3
+    pub ir: [i16; 4],
+    /// screen XY FIFO, SXY2 is the most recent entry
+    pub sxy: [[i16; 2]; 3],
+    /// screen Z FIFO, SZ3 is the most recent entry
+    pub sz: [u16; 4],
+    /// color FIFO, RGB2 is the most recent entry
+    pub rgb: [[u8; 4]; 3],
+    /// accumulators MAC0..MAC3
+    pub mac: [i32; 4],
+}
+
+impl Gte
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// The FLAG register as the CPU would read it, with the error summary bit.
+    pub fn flag(&self) -> u32
+    {
+        if self.flag & FLAG_ERROR_MASK != 0
+        {
+            self.flag | FLAG_ERROR
+        }
+        else
+        {
+            self.flag
+        }
+    }
+
+    pub fn screen_xy(&self, index: usize) -> (i16, i16)
+    {
+        (self.sxy[index][0], self.sxy[index][1])
+    }
+
+    ////////////////
+    //  COMMANDS  //
+    ////////////////
+
+    /// Perspective transformation of V0.
+    pub fn rtps(&mut self, sf: bool, lm: bool)
+    {
+        self.flag = 0;
+        self.rtp(0, sf, lm, true);
+    }
+
+    /// Perspective transformation of V0, V1 and V2. Depth cueing is only
+    /// computed for V2.
+    pub fn rtpt(&mut self, sf: bool, lm: bool)
+    {
+        self.flag = 0;
+        self.rtp(0, sf, lm, false);
+        self.rtp(1, sf, lm, false);
+        self.rtp(2, sf, lm, true);
+    }
+
+    /// Normal clipping: the z component of the cross product of the screen
+    /// FIFO triangle. Positive for a counter-clockwise winding.
+    pub fn nclip(&mut self)
+    {
+        self.flag = 0;
+        let [s0, s1, s2] = self.sxy.map(|s| [s[0] as i64, s[1] as i64]);
+        let value = s0[0] * s1[1] + s1[0] * s2[1] + s2[0] * s0[1]
+            - s0[0] * s2[1] - s1[0] * s0[1] - s2[0] * s1[1];
+        self.set_mac0(value);
+    }
+
+    /// Average of the last three screen Z values, scaled by ZSF3.
+    pub fn avsz3(&mut self)
+    {
+        self.flag = 0;
+        let sum = self.sz[1] as i64 + self.sz[2] as i64 + self.sz[3] as i64;
+        let value = self.zsf3 as i64 * sum;
+        self.set_mac0(value);
+        self.otz = self.saturate_otz(value >> 12);
+    }
+
+    /// Average of all four screen Z values, scaled by ZSF4.
+    pub fn avsz4(&mut self)
+    {
+        self.flag = 0;
+        let sum = self.sz.iter().map(|&z| z as i64).sum::<i64>();
+        let value = self.zsf4 as i64 * sum;
+        self.set_mac0(value);
+        self.otz = self.saturate_otz(value >> 12);
+    }
+
+    /// Multiply a vector by a matrix and add a translation.
+    pub fn mvmva(&mut self, sf: bool, lm: bool, mx: MvmvaMatrix, v: MvmvaVector, cv: MvmvaTranslation)
+    {
+        self.flag = 0;
+        let matrix = match mx
+        {
+            MvmvaMatrix::Rotation => self.rotation,
+            MvmvaMatrix::Light => self.light,
+            MvmvaMatrix::Color => self.color,
+            MvmvaMatrix::Reserved =>
+            {
+                let r = (self.rgbc[0] as i16) << 4;
+                [
+                    [-r, r, self.ir[0]],
+                    [self.rotation[0][2]; 3],
+                    [self.rotation[1][1]; 3],
+                ]
+            }
+        };
+        let vector = match v
+        {
+            MvmvaVector::V0 => self.v[0],
+            MvmvaVector::V1 => self.v[1],
+            MvmvaVector::V2 => self.v[2],
+            MvmvaVector::Ir => [self.ir[1], self.ir[2], self.ir[3]],
+        };
+        let translation = match cv
+        {
+            MvmvaTranslation::Translation => self.translation,
+            MvmvaTranslation::BackgroundColor => self.background_color,
+            MvmvaTranslation::FarColor => self.far_color,
+            MvmvaTranslation::None => [0; 3],
+        };
+
+        if cv == MvmvaTranslation::FarColor
+        {
+            // Hardware bug: the far color and first column only affect the
+            // flags, the result is just the last two columns.
+            for i in 0..3
+            {
+                let first = ((translation[i] as i64) << 12) + matrix[i][0] as i64 * vector[0] as i64;
+                let first = self.check_mac(i, first);
+                self.saturate_ir(i, first >> if sf { 12 } else { 0 }, false);
+                let value = self.check_mac(i, matrix[i][1] as i64 * vector[1] as i64);
+                let value = self.check_mac(i, value + matrix[i][2] as i64 * vector[2] as i64);
+                self.set_mac_ir(i, value, sf, lm);
+            }
+        }
+        else
+        {
+            self.multiply_matrix_vector(&matrix, &vector, &translation, 12, sf, lm);
+        }
+    }
+
+    /// Normal color, single vector.
+    pub fn ncs(&mut self, sf: bool, lm: bool)
+    {
+        self.flag = 0;
+        self.light_color(0, sf, lm);
+        self.push_color_from_mac();
+    }
+
+    /// Normal color with depth cueing, single vector.
+    pub fn ncds(&mut self, sf: bool, lm: bool)
+    {
+        self.flag = 0;
+        self.light_color(0, sf, lm);
+        self.multiply_by_rgbc();
+        self.interpolate_far_color(sf, lm);
+        self.push_color_from_mac();
+    }
+
+    /// Normal color with material color, single vector.
+    pub fn nccs(&mut self, sf: bool, lm: bool)
+    {
+        self.flag = 0;
+        self.light_color(0, sf, lm);
+        self.multiply_by_rgbc();
+        for i in 0..3
+        {
+            let value = (self.mac[i + 1] as i64) >> if sf { 12 } else { 0 };
+            self.set_mac_ir(i, value, false, lm);
+        }
+        self.push_color_from_mac();
+    }
+
+    /// Depth cueing of RGBC towards the far color.
+    pub fn dpcs(&mut self, sf: bool, lm: bool)
+    {
+        self.flag = 0;
+        for i in 0..3
+        {
+            let value = (self.rgbc[i] as i64) << 16;
+            self.mac[i + 1] = self.check_mac(i, value) as i32;
+        }
+        self.interpolate_far_color(sf, lm);
+        self.push_color_from_mac();
+    }
+
+    /// Square of the IR vector.
+    pub fn sqr(&mut self, sf: bool, lm: bool)
+    {
+        self.flag = 0;
+        for i in 0..3
+        {
+            let ir = self.ir[i + 1] as i64;
+            let value = self.check_mac(i, ir * ir);
+            self.set_mac_ir(i, value, sf, lm);
+        }
+    }
+
+    /// Outer product of the rotation matrix diagonal and the IR vector.
+    pub fn op(&mut self, sf: bool, lm: bool)
+    {
+        self.flag = 0;
+        let d = [self.rotation[0][0] as i64, self.rotation[1][1] as i64, self.rotation[2][2] as i64];
+        let ir = [self.ir[1] as i64, self.ir[2] as i64, self.ir[3] as i64];
+        let values = [
+            ir[2] * d[1] - ir[1] * d[2],
+            ir[0] * d[2] - ir[2] * d[0],
+            ir[1] * d[0] - ir[0] * d[1],
+        ];
+        for (i, value) in values.into_iter().enumerate()
+        {
+            let value = self.check_mac(i, value);
+            self.set_mac_ir(i, value, sf, lm);
+        }
+    }
+
+    ////////////////
+    //  INTERNAL  //
+    ////////////////
+
+    fn rtp(&mut self, index: usize, sf: bool, lm: bool, depth_cue: bool)
+    {
+        let vector = self.v[index];
+        let rotation = self.rotation;
+        let translation = self.translation;
+        // IR3 has a quirk: its flag is computed from MAC3 >> 12 even with sf=0
+        for i in 0..3
+        {
+            let mut value = (translation[i] as i64) << 12;
+            for j in 0..3
+            {
+                value = self.check_mac(i, value + rotation[i][j] as i64 * vector[j] as i64);
+            }
+            let shifted = value >> if sf { 12 } else { 0 };
+            self.mac[i + 1] = shifted as i32;
+            if i == 2
+            {
+                let lower = if lm { 0 } else { -0x8000 };
+                self.ir[3] = shifted.clamp(lower, 0x7FFF) as i16;
+                let flag_value = value >> 12;
+                if !(-0x8000..=0x7FFF).contains(&flag_value)
+                {
+                    self.flag |= FLAG_IR3_SATURATED;
+                }
+            }
+            else
+            {
+                self.ir[i + 1] = self.saturate_ir(i, shifted, lm);
+            }
+        }
+
+        // SZ3 is the unshifted MAC3 with sf=0, and MAC3 >> 12 otherwise
+        let z = (self.mac[3] as i64) >> if sf { 0 } else { 12 };
+        self.push_sz(z);
+
+        let quotient = self.divide() as i64;
+        let sx = self.set_mac0(quotient * self.ir[1] as i64 + self.ofx as i64);
+        let sy = self.set_mac0(quotient * self.ir[2] as i64 + self.ofy as i64);
+        self.push_sxy(sx >> 16, sy >> 16);
+
+        if depth_cue
+        {
+            let depth = self.set_mac0(quotient * self.dqa as i64 + self.dqb as i64);
+            self.ir[0] = self.saturate_ir0(depth >> 12);
+        }
+    }
+
+    fn divide(&mut self) -> u32
+    {
+        let (quotient, overflow) = divide(self.h, self.sz[3]);
+        if overflow
+        {
+            self.flag |= FLAG_DIVIDE_OVERFLOW;
+        }
+        quotient
+    }
+
+    /// LLM * V and then BK + LCM * IR, leaving the result in MAC and IR.
+    fn light_color(&mut self, index: usize, sf: bool, lm: bool)
+    {
+        let light = self.light;
+        let vector = self.v[index];
+        self.multiply_matrix_vector(&light, &vector, &[0; 3], 12, sf, lm);
+        let color = self.color;
+        let ir = [self.ir[1], self.ir[2], self.ir[3]];
+        let background_color = self.background_color;
+        self.multiply_matrix_vector(&color, &ir, &background_color, 12, sf, lm);
+    }
+
+    /// MAC = [R * IR1, G * IR2, B * IR3] << 4
+    fn multiply_by_rgbc(&mut self)
+    {
+        for i in 0..3
+        {
+            let value = ((self.rgbc[i] as i64) * (self.ir[i + 1] as i64)) << 4;
+            self.mac[i + 1] = self.check_mac(i, value) as i32;
+        }
+    }
+
+    /// MAC = MAC + (FC - MAC) * IR0
+    fn interpolate_far_color(&mut self, sf: bool, lm: bool)
+    {
+        let shift = if sf { 12 } else { 0 };
+        let mac = [self.mac[1] as i64, self.mac[2] as i64, self.mac[3] as i64];
+        for (i, &m) in mac.iter().enumerate()
+        {
+            let far = self.check_mac(i, (self.far_color[i] as i64) << 12);
+            let difference = self.check_mac(i, far - m);
+            self.ir[i + 1] = self.saturate_ir(i, difference >> shift, false);
+        }
+        for (i, &m) in mac.iter().enumerate()
+        {
+            let value = self.check_mac(i, self.ir[i + 1] as i64 * self.ir[0] as i64 + m);
+            self.set_mac_ir(i, value, sf, lm);
+        }
+    }
+
+    fn multiply_matrix_vector(&mut self, matrix: &[[i16; 3]; 3], vector: &[i16; 3], translation: &[i32; 3], translation_shift: u32, sf: bool, lm: bool)
+    {
+        for i in 0..3
+        {
+            let mut value = (translation[i] as i64) << translation_shift;
+            for j in 0..3
+            {
+                value = self.check_mac(i, value + matrix[i][j] as i64 * vector[j] as i64);
+            }
+            self.set_mac_ir(i, value, sf, lm);
+        }
+    }
+
+    /// Shift a MAC1..3 value by sf and store it in both MAC and IR.
+    fn set_mac_ir(&mut self, i: usize, value: i64, sf: bool, lm: bool)
+    {
+        let shifted = value >> if sf { 12 } else { 0 };
+        self.mac[i + 1] = shifted as i32;
+        self.ir[i + 1] = self.saturate_ir(i, shifted, lm);
+    }
+
+    /// Checks a MAC1..3 intermediate against the 44 bit accumulator and
+    /// sign-extends it back from 44 bits like the hardware does.
+    fn check_mac(&mut self, i: usize, value: i64) -> i64
+    {
+        if value > 0x7FF_FFFF_FFFF
+        {
+            self.flag |= MAC_POSITIVE_OVERFLOW[i];
+        }
+        else if value < -0x800_0000_0000
+        {
+            self.flag |= MAC_NEGATIVE_OVERFLOW[i];
+        }
+        (value << 20) >> 20
+    }
+
+    fn set_mac0(&mut self, value: i64) -> i64
+    {
+        if value > i32::MAX as i64
+        {
+            self.flag |= FLAG_MAC0_POSITIVE_OVERFLOW;
+        }
+        else if value < i32::MIN as i64
+        {
+            self.flag |= FLAG_MAC0_NEGATIVE_OVERFLOW;
+        }
+        self.mac[0] = value as i32;
+        value
+    }
+
+    fn saturate_ir(&mut self, i: usize, value: i64, lm: bool) -> i16
+    {
+        let lower = if lm { 0 } else { -0x8000 };
+        if value < lower || value > 0x7FFF
+        {
+            self.flag |= IR_SATURATED[i];
+        }
+        value.clamp(lower, 0x7FFF) as i16
+    }
+
+    fn saturate_ir0(&mut self, value: i64) -> i16
+    {
+        if !(0..=0x1000).contains(&value)
+        {
+            self.flag |= FLAG_IR0_SATURATED;
+        }
+        value.clamp(0, 0x1000) as i16
+    }
+
+    fn saturate_otz(&mut self, value: i64) -> u16
+    {
+        if !(0..=0xFFFF).contains(&value)
+        {
+            self.flag |= FLAG_SZ3_OTZ_SATURATED;
+        }
+        value.clamp(0, 0xFFFF) as u16
+    }
+
+    fn push_sz(&mut self, value: i64)
+    {
+        let z = self.saturate_otz(value);
+        self.sz = [self.sz[1], self.sz[2], self.sz[3], z];
+    }
+
+    fn push_sxy(&mut self, x: i64, y: i64)
+    {
+        let range = SCREEN_MIN as i64..=SCREEN_MAX as i64;
+        if !range.contains(&x)
+        {
+            self.flag |= FLAG_SX2_SATURATED;
+        }
+        if !range.contains(&y)
+        {
+            self.flag |= FLAG_SY2_SATURATED;
+        }
+        let sxy2 = [
+            x.clamp(SCREEN_MIN as i64, SCREEN_MAX as i64) as i16,
+            y.clamp(SCREEN_MIN as i64, SCREEN_MAX as i64) as i16
+        ];
+        self.sxy = [self.sxy[1], self.sxy[2], sxy2];
+    }
+
+    /// Color FIFO = [MAC1 / 16, MAC2 / 16, MAC3 / 16, CODE]
+    fn push_color_from_mac(&mut self)
+    {
+        let mut color = [0u8; 4];
+        for (i, channel) in color.iter_mut().take(3).enumerate()
+        {
+            let value = (self.mac[i + 1] >> 4) as i64;
+            if !(0..=0xFF).contains(&value)
+            {
+                self.flag |= COLOR_SATURATED[i];
+            }
+            *channel = value.clamp(0, 0xFF) as u8;
+        }
+        color[3] = self.rgbc[3];
+        self.rgb = [self.rgb[1], self.rgb[2], color];
+    }
+}
+
+/////////////////////////
+//  FORMAT CONVERSION  //
+/////////////////////////
+
+/// Converts to a 1.3.12 matrix element, saturating at the representable range.
+pub fn to_1_3_12(value: FractionalType) -> i16
+{
+    to_fraction_bits_12(value).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// Converts to an integer with 16 fraction bits, the format of OFX/OFY.
+pub fn to_fraction_bits_16(value: FractionalType) -> i32
+{
+    value.to_num::<fixed::types::I16F16>().to_bits()
+}
+
+/// Converts to an integer with 12 fraction bits, the format of TRX/TRY/TRZ
+/// when the GTE is used with sf=1.
+pub fn to_fraction_bits_12(value: FractionalType) -> i32
+{
+    value.to_num::<fixed::types::I20F12>().to_bits()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const ONE: i16 = 0x1000;
+
+    // identity rotation, the camera 1000 units behind the origin, H = 1000
+    // and the screen center at (160, 120)
+    fn camera_gte() -> Gte
+    {
+        let mut gte = Gte::new();
+        gte.rotation = [[ONE, 0, 0], [0, ONE, 0], [0, 0, ONE]];
+        gte.translation = [0, 0, 1000];
+        gte.h = 1000;
+        gte.ofx = 160 << 16;
+        gte.ofy = 120 << 16;
+        gte
+    }
+
+    #[test]
+    fn rtps_projects_v0()
+    {
+        let mut gte = camera_gte();
+        gte.v[0] = [100, -50, 0];
+        gte.rtps(true, false);
+        assert_eq!(gte.mac[1..], [100, -50, 1000]);
+        assert_eq!(gte.ir[1..], [100, -50, 1000]);
+        assert_eq!(gte.sz[3], 1000);
+        assert_eq!(gte.screen_xy(2), (260, 70));
+        assert_eq!(gte.flag(), 0);
+    }
+
+    #[test]
+    fn rtps_depth_cues()
+    {
+        let mut gte = camera_gte();
+        // IR0 = (H / SZ3 * DQA + DQB) >> 12, here 1.0 * 0.5 + 0.25
+        gte.dqa = 0x80;
+        gte.dqb = 0x40_0000;
+        gte.rtps(true, false);
+        assert_eq!(gte.mac[0], 0xC0_0000);
+        assert_eq!(gte.ir[0], 0xC00);
+        assert_eq!(gte.flag(), 0);
+    }
+
+    #[test]
+    fn rtpt_pushes_three_vertices()
+    {
+        let mut gte = camera_gte();
+        gte.v = [[100, 0, 0], [200, 0, 1000], [0, 300, 2000]];
+        gte.rtpt(true, false);
+        assert_eq!(gte.sz[1..], [1000, 2000, 3000]);
+        assert_eq!(gte.screen_xy(0), (260, 120));
+        assert_eq!(gte.screen_xy(1), (260, 120));
+        // H / SZ3 is 0x5555, a bit under a third, so 300 lands short of 100
+        assert_eq!(gte.screen_xy(2), (160, 219));
+        assert_eq!(gte.flag(), 0);
+    }
+
+    #[test]
+    fn nclip_is_positive_counterclockwise()
+    {
+        let mut gte = Gte::new();
+        gte.sxy = [[0, 0], [10, 0], [0, 10]];
+        gte.nclip();
+        assert_eq!(gte.mac[0], 100);
+        gte.sxy = [[0, 0], [0, 10], [10, 0]];
+        gte.nclip();
+        assert_eq!(gte.mac[0], -100);
+        gte.sxy = [[0, 0], [5, 5], [10, 10]];
+        gte.nclip();
+        assert_eq!(gte.mac[0], 0);
+        assert_eq!(gte.flag(), 0);
+    }
+
+    #[test]
+    fn avsz3_averages_the_last_three()
+    {
+        let mut gte = Gte::new();
+        // libgte's ZSF3 for a 1024 entry ordering table, about 1024 / 3 / 4
+        gte.zsf3 = 0x155;
+        gte.sz = [0xFFFF, 100, 200, 300];
+        gte.avsz3();
+        assert_eq!(gte.mac[0], 0x155 * 600);
+        assert_eq!(gte.otz, 49);
+        assert_eq!(gte.flag(), 0);
+    }
+
+    #[test]
+    fn avsz4_averages_all_four()
+    {
+        let mut gte = Gte::new();
+        gte.zsf4 = 0x100;
+        gte.sz = [100, 200, 300, 400];
+        gte.avsz4();
+        assert_eq!(gte.mac[0], 0x100 * 1000);
+        assert_eq!(gte.otz, 62);
+        assert_eq!(gte.flag(), 0);
+    }
+
+    #[test]
+    fn avsz_saturates_otz_and_mac0()
+    {
+        let mut gte = Gte::new();
+        gte.zsf3 = -1;
+        gte.sz = [0, 100, 200, 300];
+        gte.avsz3();
+        assert_eq!(gte.otz, 0);
+        assert_eq!(gte.flag(), FLAG_ERROR | FLAG_SZ3_OTZ_SATURATED);
+
+        gte.zsf4 = 0x7FFF;
+        gte.sz = [0xFFFF; 4];
+        gte.avsz4();
+        assert_eq!(gte.otz, 0xFFFF);
+        assert_eq!(gte.flag(), FLAG_ERROR | FLAG_SZ3_OTZ_SATURATED | FLAG_MAC0_POSITIVE_OVERFLOW);
+    }
+
+    #[test]
+    fn mac_overflow_is_flagged_and_wraps_at_44_bits()
+    {
+        let mut gte = camera_gte();
+        gte.translation = [0x7FFF_FFFF, i32::MIN, 1000];
+        gte.v[0] = [ONE, ONE, 0];
+        gte.rtps(true, false);
+        let flag = gte.flag();
+        assert_ne!(flag & FLAG_MAC1_POSITIVE_OVERFLOW, 0);
+        assert_eq!(flag & FLAG_MAC2_POSITIVE_OVERFLOW, 0);
+        assert_ne!(flag & FLAG_ERROR, 0);
+        // 0x7FF_FFFF_F000 + 0x100_0000 wrapped into the negative half
+        assert_eq!(gte.ir[1], -0x8000);
+        assert_ne!(flag & FLAG_IR1_SATURATED, 0);
+    }
+
+    #[test]
+    fn ir_saturates_to_zero_with_lm()
+    {
+        let mut gte = camera_gte();
+        gte.v[0] = [-100, 100, 0];
+        gte.rtps(true, true);
+        assert_eq!(gte.mac[1], -100);
+        assert_eq!(gte.ir[1..], [0, 100, 1000]);
+        assert_eq!(gte.flag(), FLAG_ERROR | FLAG_IR1_SATURATED);
+
+        gte.rtps(true, false);
+        assert_eq!(gte.ir[1], -100);
+        assert_eq!(gte.flag(), 0);
+    }
+
+    #[test]
+    fn ir3_flag_ignores_sf()
+    {
+        // with sf=0 IR3 saturates, but its flag is computed from MAC3 >> 12
+        let mut gte = camera_gte();
+        gte.translation = [0, 0, 0];
+        gte.v[0] = [0, 0, 0x100];
+        gte.rtps(false, false);
+        assert_eq!(gte.mac[3], 0x10_0000);
+        assert_eq!(gte.ir[3], 0x7FFF);
+        assert_eq!(gte.flag() & FLAG_IR3_SATURATED, 0);
+        // and SZ3 is MAC3 >> 12 with sf=0
+        assert_eq!(gte.sz[3], 0x100);
+    }
+
+    #[test]
+    fn sz3_saturates()
+    {
+        let mut gte = camera_gte();
+        gte.translation = [0, 0, -1000];
+        gte.rtps(true, false);
+        assert_eq!(gte.sz[3], 0);
+        assert_ne!(gte.flag() & FLAG_SZ3_OTZ_SATURATED, 0);
+    }
+
+    #[test]
+    fn ir0_and_color_saturation_are_not_errors()
+    {
+        let mut gte = camera_gte();
+        gte.dqb = -(1 << 12);
+        gte.rtps(true, false);
+        assert_eq!(gte.ir[0], 0);
+        assert_eq!(gte.flag(), FLAG_IR0_SATURATED);
+    }
+
+    #[test]
+    fn unr_table_matches_hardware()
+    {
+        assert_eq!(
+            UNR_TABLE[..16],
+            [0xFF, 0xFD, 0xFB, 0xF9, 0xF7, 0xF5, 0xF3, 0xF1, 0xEF, 0xEE, 0xEC, 0xEA, 0xE8, 0xE6, 0xE4, 0xE3]
+        );
+        assert_eq!(UNR_TABLE[0x100], 0);
+    }
+
+    #[test]
+    fn divide_known_quotients()
+    {
+        assert_eq!(divide(1000, 1000), (0x1_0000, false));
+        assert_eq!(divide(500, 1000), (0x8000, false));
+        assert_eq!(divide(1, 2), (0x8000, false));
+        assert_eq!(divide(0, 5), (0, false));
+        assert_eq!(divide(1000, 3000), (0x5555, false));
+        assert_eq!(divide(0xFFFF, 0x8000), (0x1_FFFE, false));
+        // just under the overflow
+        assert_eq!(divide(1999, 1000), (0x1_FFBF, false));
+    }
+
+    #[test]
+    fn divide_overflows_at_twice_sz3()
+    {
+        assert_eq!(divide(2000, 1000), (DIVIDE_MAX, true));
+        assert_eq!(divide(0xFFFF, 1), (DIVIDE_MAX, true));
+        assert_eq!(divide(5, 0), (DIVIDE_MAX, true));
+        // 0 >= 2 * 0, so even this overflows
+        assert_eq!(divide(0, 0), (DIVIDE_MAX, true));
+    }
+
+    #[test]
+    fn rtps_flags_divide_overflow()
+    {
+        let mut gte = camera_gte();
+        gte.translation = [0, 0, 100];
+        gte.v[0] = [10, -10, 0];
+        gte.rtps(true, false);
+        // 0x1FFFF * 10 is a bit under 20 pixels
+        assert_eq!(gte.screen_xy(2), (179, 100));
+        assert_eq!(gte.flag(), FLAG_ERROR | FLAG_DIVIDE_OVERFLOW);
+    }
+
+    #[test]
+    fn screen_coordinates_clamp()
+    {
+        let mut gte = camera_gte();
+        gte.v[0] = [863, -1144, 0];
+        gte.rtps(true, false);
+        assert_eq!(gte.screen_xy(2), (SCREEN_MAX as i16, SCREEN_MIN as i16));
+        assert_eq!(gte.flag(), 0);
+
+        gte.v[0] = [864, -1145, 0];
+        gte.rtps(true, false);
+        assert_eq!(gte.screen_xy(2), (SCREEN_MAX as i16, SCREEN_MIN as i16));
+        assert_eq!(gte.flag(), FLAG_ERROR | FLAG_SX2_SATURATED | FLAG_SY2_SATURATED);
+
+        gte.v[0] = [2000, -2000, 0];
+        gte.rtps(true, false);
+        assert_eq!(gte.screen_xy(2), (1023, -1024));
+        assert_eq!(gte.flag(), FLAG_ERROR | FLAG_SX2_SATURATED | FLAG_SY2_SATURATED);
+    }
+}
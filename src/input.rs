@@ -0,0 +1,230 @@
+use crate::camera::Camera;
+use crate::fractional_type::FractionalType;
+use crate::math::Vec3;
+use num_traits::identities::One;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::keyboard::KeyboardState;
+use bindings::{Action, Bindings};
+
+pub mod bindings;
+
+// Input is polled once per frame instead of reacting to KeyDown events, so
+// movement doesn't depend on the key repeat rate, and is scaled by the frame's
+// delta time so it doesn't depend on the frame rate either.
+
+/// How fast the free-fly controls move and turn the camera.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct InputSettings
+{
+    /// world units per second
+    pub move_speed: FractionalType,
+    /// fractional_type::ANGLE_ONE_TURN units per second
+    pub turn_speed: i32,
+    /// fractional_type::ANGLE_ONE_TURN units per pixel of mouse movement
+    pub mouse_look_step: i32,
+    /// analog stick values closer to the center than this are ignored
+    pub stick_dead_zone: i16,
+}
+
+impl Default for InputSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            move_speed: FractionalType::from_num(2),
+            // a quarter turn per second
+            turn_speed: 1024,
+            mouse_look_step: 4,
+            stick_dead_zone: 8000,
+        }
+    }
+}
+
+impl InputSettings
+{
+    // the names set takes, also the command line's
+    const NAMES: &'static [&'static str] = &["move_speed", "turn_speed", "mouse_look_step", "stick_dead_zone"];
+
+    /// Applies the `--name=value` arguments naming an input setting, e.g.
+    /// --move_speed=4, and returns the rest for the render preferences.
+    pub fn apply_args<I: IntoIterator<Item = String>>(&mut self, args: I) -> Result<Vec<String>, String>
+    {
+        let mut rest = Vec::new();
+        for arg in args
+        {
+            match arg.strip_prefix("--").and_then(|setting| setting.split_once('='))
+            {
+                Some((name, value)) if Self::NAMES.contains(&name) =>
+                    self.set(name, value).map_err(|message| format!("{}: {}", arg, message))?,
+                _ => rest.push(arg),
+            }
+        }
+        Ok(rest)
+    }
+
+    /// Sets one setting from its name and value.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String>
+    {
+        fn parse_positive<T: std::str::FromStr + PartialOrd + Default>(value: &str) -> Result<T, String>
+        {
+            value.parse()
+                .ok()
+                .filter(|parsed| *parsed > T::default())
+                .ok_or_else(|| format!("expected a positive number, got `{}`", value))
+        }
+
+        match name
+        {
+            "move_speed" => self.move_speed = parse_positive(value)?,
+            "turn_speed" => self.turn_speed = parse_positive(value)?,
+            "mouse_look_step" => self.mouse_look_step = parse_positive(value)?,
+            "stick_dead_zone" => self.stick_dead_zone = parse_positive(value)?,
+            _ => return Err(format!("unknown setting `{}`", name)),
+        }
+        Ok(())
+    }
+}
+
+/// Movement and turning asked for this frame. Every axis goes from -1 to 1,
+/// digital inputs are always at the ends, analog ones anywhere in between.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Motion
+{
+    /// in camera space: +x is right, +y is up and +z is forward
+    pub movement: Vec3,
+    /// positive turns left, see Camera::rotate_yaw
+    pub yaw: FractionalType,
+    /// positive turns up
+    pub pitch: FractionalType,
+    /// positive rolls clockwise
+    pub roll: FractionalType,
+}
+
+impl Motion
+{
+    pub fn from_keyboard(keyboard: &KeyboardState, bindings: &Bindings) -> Self
+    {
+        let axis = |positive: Action, negative: Action| digital_axis(
+            bindings.is_held(positive, keyboard),
+            bindings.is_held(negative, keyboard)
+        );
+        Self {
+            movement: Vec3::new(
+                axis(Action::MoveRight, Action::MoveLeft),
+                axis(Action::MoveUp, Action::MoveDown),
+                axis(Action::MoveForward, Action::MoveBack)
+            ),
+            yaw: axis(Action::YawLeft, Action::YawRight),
+            pitch: axis(Action::PitchUp, Action::PitchDown),
+            roll: axis(Action::RollClockwise, Action::RollCounterclockwise),
+        }
+    }
+
+    /// Adds a PSX-style pad: the D-pad and left stick move, the right stick
+    /// looks around, L1/R1 move down/up and the triggers roll.
+    pub fn add_controller(&mut self, controller: &GameController, settings: &InputSettings)
+    {
+        let stick = |axis: Axis| analog_axis(controller.axis(axis), settings.stick_dead_zone);
+        let button = |positive: Button, negative: Button| digital_axis(controller.button(positive), controller.button(negative));
+        let movement = Vec3::new(
+            stick(Axis::LeftX) + button(Button::DPadRight, Button::DPadLeft),
+            button(Button::RightShoulder, Button::LeftShoulder),
+            // stick y points down
+            -stick(Axis::LeftY) + button(Button::DPadUp, Button::DPadDown)
+        );
+        *self = Self {
+            movement: self.movement + movement,
+            yaw: self.yaw - stick(Axis::RightX),
+            pitch: self.pitch - stick(Axis::RightY),
+            roll: self.roll + stick(Axis::TriggerLeft) - stick(Axis::TriggerRight),
+        }
+        .clamped();
+    }
+
+    fn clamped(self) -> Self
+    {
+        let clamp = |value: FractionalType| value.clamp(-FractionalType::one(), FractionalType::one());
+        Self {
+            movement: Vec3::from_array(self.movement.to_array().map(clamp)),
+            yaw: clamp(self.yaw),
+            pitch: clamp(self.pitch),
+            roll: clamp(self.roll),
+        }
+    }
+
+    /// Flies `camera` for `delta_time` seconds.
+    pub fn apply(&self, camera: &mut Camera, settings: &InputSettings, delta_time: FractionalType)
+    {
+        if self.movement != Vec3::zero()
+        {
+            camera.translate_local(self.movement * (settings.move_speed * delta_time));
+        }
+        let turn = FractionalType::from_num(settings.turn_speed) * delta_time;
+        let angle = |amount: FractionalType| (amount * turn).round().to_num::<i32>();
+        let (yaw, pitch, roll) = (angle(self.yaw), angle(self.pitch), angle(self.roll));
+        if yaw != 0
+        {
+            camera.rotate_yaw(yaw);
+        }
+        if pitch != 0
+        {
+            camera.rotate_pitch(pitch);
+        }
+        if roll != 0
+        {
+            camera.rotate_roll(roll);
+        }
+    }
+}
+
+/// Turns the camera by a relative mouse movement in pixels.
+pub fn mouse_look(camera: &mut Camera, settings: &InputSettings, xrel: i32, yrel: i32)
+{
+    // moving right turns right, moving down looks down
+    camera.rotate_yaw(-xrel * settings.mouse_look_step);
+    camera.rotate_pitch(-yrel * settings.mouse_look_step);
+}
+
+fn digital_axis(positive: bool, negative: bool) -> FractionalType
+{
+    FractionalType::from_num(positive as i32 - negative as i32)
+}
+
+// maps the stick range outside the dead zone to 0..1 so small movements past
+// it don't jump
+fn analog_axis(value: i16, dead_zone: i16) -> FractionalType
+{
+    let dead_zone = dead_zone.clamp(0, i16::MAX - 1) as i32;
+    let value = value as i32;
+    let magnitude = (value.abs() - dead_zone).max(0);
+    FractionalType::from_num(magnitude * value.signum()) / FractionalType::from_num(i16::MAX as i32 - dead_zone)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn arguments_set_input_settings_and_pass_the_rest_on()
+    {
+        let mut settings = InputSettings::default();
+        let rest = settings.apply_args(["--turn_speed=2048", "--culling=front", "model.gltf", "--move_speed=0.5"].map(String::from))
+            .unwrap();
+        assert_eq!(settings.turn_speed, 2048);
+        assert_eq!(settings.move_speed, FractionalType::from_num(0.5));
+        assert_eq!(rest, ["--culling=front", "model.gltf"].map(String::from));
+    }
+
+    #[test]
+    fn input_settings_must_be_positive()
+    {
+        let mut settings = InputSettings::default();
+        for arg in ["--move_speed=0", "--turn_speed=-5", "--stick_dead_zone=lots"]
+        {
+            let error = settings.apply_args([arg.to_string()]).unwrap_err();
+            assert!(error.contains("expected a positive number"), "{}", error);
+        }
+        assert_eq!(settings, InputSettings::default());
+    }
+}
@@ -0,0 +1,330 @@
+// Action based key bindings. Everything the viewer reacts to is an Action,
+// and keys are only looked up here, so people with AZERTY, Dvorak etc. can
+// rebind them in a config file instead of editing the code.
+//
+// The config file has one action per line, followed by the names of the keys
+// that trigger it as SDL names them (the label on the key), e.g.
+//
+//   # move with ZQSD on AZERTY
+//   MoveForward = Z
+//   MoveLeft = Q
+//   YawLeft = Left, K
+//
+// Actions missing from the file keep their default keys.
+
+use sdl2::keyboard::{KeyboardState, Keycode, Scancode};
+use std::collections::HashMap;
+
+pub const DEFAULT_PATH: &str = "bindings.cfg";
+
+macro_rules! actions
+{
+    ($($action:ident => [$($key:ident),*],)*) =>
+    {
+        #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+        pub enum Action
+        {
+            $($action,)*
+        }
+
+        impl Action
+        {
+            pub const ALL: &'static [Action] = &[$(Action::$action,)*];
+
+            /// The name used in the config file.
+            pub fn name(self) -> &'static str
+            {
+                match self
+                {
+                    $(Action::$action => stringify!($action),)*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Action>
+            {
+                Self::ALL.iter().copied().find(|action| action.name() == name)
+            }
+
+            fn default_keys(self) -> &'static [Keycode]
+            {
+                match self
+                {
+                    $(Action::$action => &[$(Keycode::$key),*],)*
+                }
+            }
+        }
+    };
+}
+
+actions!
+{
+    // held down, polled every frame
+    MoveForward => [W],
+    MoveBack => [S],
+    MoveLeft => [A],
+    MoveRight => [D],
+    MoveUp => [Space],
+    MoveDown => [C],
+    YawLeft => [K],
+    YawRight => [Semicolon],
+    PitchUp => [O],
+    PitchDown => [L],
+    RollClockwise => [Q],
+    RollCounterclockwise => [E],
+    // triggered once per key press
+    Quit => [Escape],
+    ToggleOrbit => [Tab],
+    ToggleAutoRotate => [R],
+    FrameSelection => [F],
+    NextModel => [Period],
+    ToggleMouseLook => [M],
+    ToggleGte => [G],
+    ToggleDivideMode => [H],
+    ToggleProjection => [P],
+    ZoomIn => [RightBracket],
+    ZoomOut => [LeftBracket],
+    CycleRenderMode => [B],
+    RenderPoints => [Num1],
+    RenderWireframe => [Num2],
+    RenderFlat => [Num3],
+    RenderGouraud => [Num4],
+    RenderTextured => [Num5],
+    ToggleWireframe => [X],
+    ToggleVertices => [V],
+    ToggleDithering => [F4],
+    ToggleVertexSnapping => [F5],
+    CycleTextureMapping => [F6],
+    CycleTextureFiltering => [F7],
+    CycleCulling => [F8],
+    CycleSortMode => [F9],
+    CycleSkinning => [Quote],
+    NextAnimation => [Slash],
+    ToggleAnimation => [Return],
+    ScrubAnimationBack => [Left],
+    ScrubAnimationForward => [Right],
+    ToggleAnimationLoop => [Backslash],
+    SlowerAnimation => [Minus],
+    FasterAnimation => [Equals],
+    ToggleFog => [F10],
+    CycleColorDepth => [F11],
+    SavePreferences => [F2],
+    CycleHorizontalResolution => [N],
+    ToggleVerticalResolution => [Y],
+    ToggleIntegerScaling => [I],
+    ToggleInterlacing => [J],
+    ToggleDrawToDisplay => [U],
+    CycleDeinterlace => [Z],
+    CycleFrameRate => [T],
+    ToggleOverlay => [F3],
+    Screenshot => [F12],
+}
+
+#[derive(Debug)]
+pub enum BindingsError
+{
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl std::fmt::Display for BindingsError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            BindingsError::Io(error) => write!(f, "can't read bindings: {}", error),
+            BindingsError::Parse { line, message } => write!(f, "bindings line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for BindingsError {}
+
+impl From<std::io::Error> for BindingsError
+{
+    fn from(error: std::io::Error) -> Self
+    {
+        BindingsError::Io(error)
+    }
+}
+
+pub struct Bindings
+{
+    actions: HashMap<Keycode, Action>,
+}
+
+impl Default for Bindings
+{
+    fn default() -> Self
+    {
+        let actions = Action::ALL.iter()
+            .flat_map(|&action| action.default_keys().iter().map(move |&key| (key, action)))
+            .collect();
+        Self { actions }
+    }
+}
+
+impl Bindings
+{
+    /// The default bindings with the ones in the file at `path` applied on
+    /// top.
+    pub fn load(path: &std::path::Path) -> Result<Self, BindingsError>
+    {
+        let mut bindings = Self::default();
+        bindings.apply_config(&std::fs::read_to_string(path)?)?;
+        Ok(bindings)
+    }
+
+    /// Loads `path` if it exists, falling back to the defaults otherwise. A
+    /// broken file is reported and ignored so the viewer still starts.
+    pub fn load_or_default(path: &std::path::Path) -> Self
+    {
+        match Self::load(path)
+        {
+            Ok(bindings) => bindings,
+            Err(BindingsError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(error) =>
+            {
+                eprintln!("{}, using the default key bindings", error);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn apply_config(&mut self, config: &str) -> Result<(), BindingsError>
+    {
+        for (index, line) in config.lines().enumerate()
+        {
+            let parse_error = |message: String| BindingsError::Parse { line: index + 1, message };
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty()
+            {
+                continue;
+            }
+            let (name, keys) = line.split_once('=')
+                .ok_or_else(|| parse_error(format!("expected `Action = Key, ...`, got `{}`", line)))?;
+            let action = Action::from_name(name.trim())
+                .ok_or_else(|| parse_error(format!("unknown action `{}`", name.trim())))?;
+            let keys = keys.split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(|key| Keycode::from_name(key).ok_or_else(|| parse_error(format!("unknown key `{}`", key))))
+                .collect::<Result<Vec<_>, _>>()?;
+            self.bind(action, &keys);
+        }
+        Ok(())
+    }
+
+    /// Replaces the keys of `action`. A key can only trigger one action, so
+    /// it's taken away from whatever it did before.
+    pub fn bind(&mut self, action: Action, keys: &[Keycode])
+    {
+        self.actions.retain(|_, bound| *bound != action);
+        for &key in keys
+        {
+            self.actions.insert(key, action);
+        }
+    }
+
+    /// The action a key press triggers, if any.
+    pub fn action(&self, key: Keycode) -> Option<Action>
+    {
+        self.actions.get(&key).copied()
+    }
+
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = Keycode> + '_
+    {
+        self.actions.iter()
+            .filter(move |(_, bound)| **bound == action)
+            .map(|(key, _)| *key)
+    }
+
+    /// Whether any key bound to `action` is held down.
+    pub fn is_held(&self, action: Action, keyboard: &KeyboardState) -> bool
+    {
+        self.keys(action)
+            .filter_map(Scancode::from_keycode)
+            .any(|scancode| keyboard.is_scancode_pressed(scancode))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn keys(bindings: &Bindings, action: Action) -> Vec<Keycode>
+    {
+        let mut keys: Vec<_> = bindings.keys(action).collect();
+        keys.sort_by_key(|key| key.name());
+        keys
+    }
+
+    fn parse_error(config: &str) -> (usize, String)
+    {
+        match Bindings::default().apply_config(config)
+        {
+            Err(BindingsError::Parse { line, message }) => (line, message),
+            other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn every_action_has_a_default_key()
+    {
+        let bindings = Bindings::default();
+        for &action in Action::ALL
+        {
+            assert!(bindings.keys(action).next().is_some(), "{} has no key", action.name());
+            assert_eq!(Action::from_name(action.name()), Some(action));
+        }
+    }
+
+    #[test]
+    fn config_rebinds_actions()
+    {
+        let mut bindings = Bindings::default();
+        bindings.apply_config("
+            # move with ZQSD on AZERTY
+            MoveForward = Z
+            MoveLeft = Q   # strafe
+            YawLeft = Left, K
+
+            ToggleOverlay =
+        ").unwrap();
+        assert_eq!(keys(&bindings, Action::MoveForward), [Keycode::Z]);
+        assert_eq!(keys(&bindings, Action::YawLeft), [Keycode::K, Keycode::Left]);
+        assert_eq!(bindings.action(Keycode::Q), Some(Action::MoveLeft));
+        // the old keys are free, the ones taken from other actions moved
+        assert_eq!(bindings.action(Keycode::W), None);
+        assert_eq!(bindings.action(Keycode::Z), Some(Action::MoveForward));
+        assert_eq!(bindings.action(Keycode::Left), Some(Action::YawLeft));
+        assert!(keys(&bindings, Action::ToggleOverlay).is_empty());
+        // actions missing from the file keep their defaults
+        assert_eq!(keys(&bindings, Action::MoveBack), [Keycode::S]);
+    }
+
+    #[test]
+    fn unknown_actions_and_keys_are_errors()
+    {
+        let (line, message) = parse_error("MoveForward = W\nJump = Space\n");
+        assert_eq!(line, 2);
+        assert!(message.contains("unknown action `Jump`"), "{}", message);
+        let (line, message) = parse_error("\n\nMoveForward = W, Hyperspace");
+        assert_eq!(line, 3);
+        assert!(message.contains("unknown key `Hyperspace`"), "{}", message);
+    }
+
+    #[test]
+    fn malformed_lines_are_errors()
+    {
+        let (line, message) = parse_error("# comment\nMoveForward W\n");
+        assert_eq!(line, 2);
+        assert!(message.contains("expected `Action = Key, ...`"), "{}", message);
+        // nothing after a bad line is applied
+        let mut bindings = Bindings::default();
+        assert!(bindings.apply_config("MoveForward = Up\n= W\nMoveBack = Down").is_err());
+        assert_eq!(keys(&bindings, Action::MoveForward), [Keycode::Up]);
+        assert_eq!(keys(&bindings, Action::MoveBack), [Keycode::S]);
+    }
+}
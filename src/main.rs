@@ -1,547 +1,530 @@
+pub mod bounds;
+pub mod camera;
+pub mod frame_clock;
 pub mod fractional_type;
-
+pub mod gte;
+pub mod input;
+pub mod math;
+pub mod model;
+pub mod projection;
+pub mod renderer;
+
+use bounds::Bounds;
+use camera::{Camera, DivideMode};
+use camera::orbit::Orbit;
 use fractional_type::FractionalType;
-use num_traits::identities::Zero;
-use rulinalg::vector::Vector;
+use frame_clock::{FrameClock, FrameRate};
+use input::{InputSettings, Motion};
+use input::bindings::{Action, Bindings};
+use math::Vec3;
+use model::Model;
+use model::animation::Playback;
+use projection::ProjectionMode;
+use renderer::display;
+use renderer::framebuffer::{Field, Framebuffer};
+use renderer::lighting::DirectionalLight;
+use renderer::overlay;
+use renderer::preferences::{self, Deinterlace, RenderMode, RenderPreferences};
+use renderer::texture::Texture;
 use sdl2::pixels::Color;
 
-const RESOLUTION_X: u32 = 640;
-const RESOLUTION_Y: u32 = 480;
-const FOCAL_LENGTH: f32 = 1.0;
-
-fn to_proper_inf(num: FractionalType) -> FractionalType
-{
-    if num >= FractionalType::zero()
-    {
-        // TODO: had to change this to 10000 and -10000 or else sdl2 would 
-        // allocate a 2^31^2 frame buffer and run out of memory
-        FractionalType::from_num(10000)//i32::MAX
-    }
-    else
-    {
-        FractionalType::from_num(-10000)//i32::MIN
-    }
-}
+// how far dragging the mouse by one pixel orbits the camera, 512 pixels per turn
+const ORBIT_MOUSE_STEP: i32 = 8;
+// how far one key press moves the projection plane, in pixels
+const PROJECTION_H_STEP: i32 = 16;
 
-fn homogenous_image_coordinates_to_image_coordinates(homogeneous_coordinates: &Vector<FractionalType>) -> Vector<FractionalType>
+// zooms in for a positive step: moves the projection plane by `step` pixels
+// in perspective, or changes the scale by `step` pixels per world unit in
+// orthographic
+fn change_projection_zoom(camera: &mut Camera, step: i32)
 {
-    if homogeneous_coordinates[2] == FractionalType::zero()
-    {
-        rulinalg::vector![
-            to_proper_inf(homogeneous_coordinates[0]),
-            to_proper_inf(homogeneous_coordinates[1])
-        ]
-    }
-    else
+    let mut projection = *camera.projection();
+    let step = FractionalType::from_num(step);
+    let min = FractionalType::from_num(PROJECTION_H_STEP);
+    match projection.mode
     {
-        rulinalg::vector![
-            homogeneous_coordinates[0] / homogeneous_coordinates[2],
-            homogeneous_coordinates[1] / homogeneous_coordinates[2]
-        ]
+        ProjectionMode::Perspective =>
+        {
+            projection.h = (projection.h + step).max(min);
+            println!("H = {}, horizontal fov = {} units", projection.h, projection.fov());
+        }
+        ProjectionMode::Orthographic =>
+        {
+            projection.scale = (projection.scale + step).max(min);
+            println!("orthographic scale = {} pixels per unit", projection.scale);
+        }
     }
+    camera.set_projection(projection);
 }
 
-// struct for a triangle with a flat bottom or a flat top
-struct FlatTriangle<'a>
+// saves the framebuffer as a PNG in the working directory, named after the
+// current time
+fn save_screenshot(framebuffer: &Framebuffer) -> Result<String, Box<dyn std::error::Error>>
 {
-    v_odd: &'a Vector<FractionalType>,
-    v_left: &'a Vector<FractionalType>,
-    v_right: &'a Vector<FractionalType>
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let pixels = framebuffer.pixels();
+    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_millis();
+    let path = format!("screenshot-{}.png", timestamp);
+
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(std::fs::File::create(&path)?), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(pixels)?;
+    Ok(path)
 }
 
-struct NonFlatTriangle<'a>
+// what the debug overlay shows, read before the overlay itself is drawn
+fn overlay_lines(
+    framebuffer: &Framebuffer,
+    camera: &Camera,
+    clock: &FrameClock,
+    orbit: Option<&Orbit>,
+    preferences: &RenderPreferences,
+    model: &Model,
+    playback: &Playback
+) -> Vec<String>
 {
-    top: &'a Vector<FractionalType>,
-    mid: &'a Vector<FractionalType>,
-    bottom: &'a Vector<FractionalType>
-}
-
-enum TriangleType<'a>
-{
-    FlatTop(FlatTriangle<'a>),
-    FlatBottom(FlatTriangle<'a>),
-    NonFlat(NonFlatTriangle<'a>),
-    NotATriangle
-}
-
-fn categorize_triangle<'a>(v0: &'a Vector<FractionalType>, v1: &'a Vector<FractionalType>, v2: &'a Vector<FractionalType>) -> TriangleType<'a>
-{
-    // assert no two occupy the same position
-    if v0[0] == v1[0] &&
-        v0[1] == v1[1]
-    {
-        return TriangleType::NotATriangle;
-    }
-    if v0[0] == v2[0] &&
-        v0[1] == v2[1]
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let stats = framebuffer.stats;
+    let position = camera.position();
+    let forward = camera.forward();
+    let projection = match camera.projection().mode
     {
-        return TriangleType::NotATriangle;
-    }
-    if v2[0] == v1[0] &&
-        v2[1] == v1[1]
+        ProjectionMode::Perspective => format!(
+            "perspective H {} fov {:.1} deg",
+            camera.projection().h.round(),
+            camera.fov() as f32 * 360.0 / fractional_type::ANGLE_ONE_TURN as f32
+        ),
+        ProjectionMode::Orthographic => format!("orthographic {:.2} px/unit", camera.projection().scale),
+    };
+    let control = match orbit
     {
-        return TriangleType::NotATriangle;
-    }
-
-    // assert they are not colinear horizontally
-    if v0[1] == v1[1] &&
-        v1[1] == v2[1]
+        Some(orbit) if orbit.auto_rotate => "orbit (auto-rotate)",
+        Some(_) => "orbit",
+        None => "free-fly",
+    };
+    let animation = match playback.animation.and_then(|index| model.animations.get(index).map(|animation| (index, animation)))
     {
-        return TriangleType::NotATriangle;
-    }
-    // handle the case where they are colinear along other lines
-    // after the NonFlat is handled, which makes the colinear visible
-    // by the above cases
-
-    let mut vs: Vec<&Vector<FractionalType>> = vec![v0, v1, v2];
-    vs.sort_by(|a, b| b[1].cmp(&a[1]));
- 
-    if vs[0][1] == vs[1][1]
-    {
-        // flat bottom
-        if vs[0][0] < vs[1][0]
-        {
-            // vs[0] is left
-            TriangleType::FlatBottom(FlatTriangle{
-                v_odd: vs[2],
-                v_left: vs[0],
-                v_right: vs[1]
-            })
-        }
-        else
-        {
-            // vs[0] is right
-            TriangleType::FlatBottom(FlatTriangle{
-                v_odd: vs[2],
-                v_left: vs[1],
-                v_right: vs[0]
-            })
-        }
-    }
-    else if vs[1][1] == vs[2][1]
-    {
-        //flat top
-        if vs[1][0] < vs[2][0]
+        Some((index, animation)) => format!(
+            "anim {} {:.2}/{:.2} s, {}, loop {}, speed {}x",
+            animation.name.clone().unwrap_or_else(|| format!("#{}", index)),
+            playback.time,
+            animation.duration,
+            if playback.playing { "playing" } else { "paused" },
+            on_off(playback.looping),
+            playback.speed
+        ),
+        None if model.animations.is_empty() => "anim none".to_string(),
+        None => "anim rest pose".to_string(),
+    };
+    vec![
+        format!("{}", clock.stats()),
+        format!("target {}{}", clock.rate(), if clock.vsync() { ", paced by vsync" } else { "" }),
+        format!("prims {} submitted, {} culled, {} drawn", stats.primitives_submitted, stats.primitives_culled, stats.primitives_drawn),
+        format!("pixels {} filled", stats.pixels_filled),
+        format!("camera {:.2} {:.2} {:.2} ({})", position.x, position.y, position.z, control),
+        format!("facing {:.2} {:.2} {:.2}", forward.x, forward.y, forward.z),
+        projection,
+        format!("gte {}, divide {:?}", on_off(camera.use_gte), camera.divide_mode),
+        format!(
+            "mode {}, wireframe {}, vertices {}",
+            preferences.render_mode,
+            on_off(preferences.wireframe_overlay),
+            on_off(preferences.vertex_overlay)
+        ),
+        format!(
+            "{}x{} {}, dither {}, snap {}, fog {}",
+            preferences.resolution.0,
+            preferences.resolution.1,
+            preferences.color_depth,
+            on_off(preferences.dithering),
+            on_off(preferences.vertex_snapping),
+            on_off(preferences.fog)
+        ),
+        if preferences.is_interlaced()
         {
-            // vs[1] is left
-            TriangleType::FlatBottom(FlatTriangle{
-                v_odd: vs[0],
-                v_left: vs[1],
-                v_right: vs[2]
-            })
+            format!("interlaced, {}, draw to display {}", preferences.deinterlace, on_off(preferences.draw_to_display))
         }
         else
         {
-            // vs[2] is right
-            TriangleType::FlatBottom(FlatTriangle{
-                v_odd: vs[0],
-                v_left: vs[2],
-                v_right: vs[1]
-            })
-        }
-    }
-    else
-    {
-        TriangleType::NonFlat(NonFlatTriangle{
-            top: vs[2],
-            mid: vs[1],
-            bottom: vs[0]
-        })
-    }
+            "progressive".to_string()
+        },
+        format!(
+            "texture {} {}, cull {}, sort {}",
+            preferences.texture_mapping,
+            preferences.texture_filtering,
+            preferences.culling,
+            preferences.sort_mode
+        ),
+        format!("skinning {}", preferences.skinning),
+        animation,
+    ]
 }
 
-fn rasterize_flat_bottom_triangle(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, flat_bottom: &FlatTriangle, c: &Color)
+// loads a model along with its bounds, an empty model gets a point at the
+// origin
+fn load_model(path: &str) -> Result<(Model, Bounds), model::ModelError>
 {
-    let mut row_coordinate = (flat_bottom.v_odd[0] - FractionalType::from_num(0.5)).ceil() + FractionalType::from_num(0.5);
-    while row_coordinate < flat_bottom.v_left[0]
-    {
-        let col_coord_reciprocal_slope = (flat_bottom.v_odd[0] - flat_bottom.v_left[0]) / (flat_bottom.v_odd[1] - flat_bottom.v_left[1]);
-        let mut col_coord = col_coord_reciprocal_slope * flat_bottom.v_odd[1] + flat_bottom.v_odd[0] - col_coord_reciprocal_slope * row_coordinate;
-        col_coord = (col_coord - FractionalType::from_num(0.5)).ceil() + FractionalType::from_num(0.5);
-        //let mut col_coordinate = 
-        let col_guard_reciprocal_slope = (flat_bottom.v_odd[0] - flat_bottom.v_right[0]) / (flat_bottom.v_odd[1] - flat_bottom.v_right[1]);
-        let col_guard = col_guard_reciprocal_slope * flat_bottom.v_odd[1] + flat_bottom.v_odd[0] - col_guard_reciprocal_slope * row_coordinate;
-        while col_coord < col_guard
-        {
-            println!("drawing point: ({}, {})", col_coord.to_num::<u32>(), row_coordinate.to_num::<u32>());
-            let _ = canvas.draw_point((col_coord.to_num(), row_coordinate.to_num()));
-            col_coord = col_coord + FractionalType::from_num(1);
-        }
-        row_coordinate = row_coordinate + FractionalType::from_num(1);
-    }
+    let model = Model::load(std::path::Path::new(path))?;
+    let bounds = model.bounds().or_else(|| Bounds::from_points(&[Vec3::zero()])).unwrap();
+    Ok((model, bounds))
 }
 
-fn rasterize_flat_top_triangle(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, flat_top: &FlatTriangle, c: &Color)
+// frames the whole model, keeping the orbit mode and its auto-rotation
+fn frame_model(camera: &mut Camera, orbit: &mut Option<Orbit>, bounds: &Bounds)
 {
-
-}
-
-fn rasterize_non_flat_triangle(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, non_flat: &NonFlatTriangle, c: &Color)
-{
-    let reciprocal_slope = (non_flat.top[0] - non_flat.bottom[0]) / (non_flat.top[1] - non_flat.bottom[1]);
-    let intersection: Vector<FractionalType> = rulinalg::vector![
-        reciprocal_slope * non_flat.top[1] + non_flat.top[0] - reciprocal_slope * non_flat.mid[1],
-        non_flat.mid[1]
-    ];
-    if intersection[0] > non_flat.mid[0]
+    camera.frame(&bounds.sphere);
+    if let Some(orbit) = orbit
     {
-        // intersection is right
-        rasterize_flat_bottom_triangle(
-            canvas,
-            &FlatTriangle{
-                v_odd: non_flat.top,
-                v_left: non_flat.mid,
-                v_right: &intersection
-            },
-            c);
-        rasterize_flat_top_triangle(
-            canvas,
-            &FlatTriangle{
-                v_odd: non_flat.bottom,
-                v_left: non_flat.mid,
-                v_right: &intersection
-            },
-            c);
-    }
-    else
-    {
-        // intersection is left
-        rasterize_flat_bottom_triangle(
-            canvas,
-            &FlatTriangle{
-                v_odd: non_flat.top,
-                v_left: &intersection,
-                v_right: non_flat.mid
-            },
-            c);
-        rasterize_flat_top_triangle(
-            canvas,
-            &FlatTriangle{
-                v_odd: non_flat.bottom,
-                v_left: &intersection,
-                v_right: non_flat.mid
-            },
-            c);
+        let auto_rotate = orbit.auto_rotate;
+        *orbit = Orbit::from_camera(camera, bounds.sphere.center);
+        orbit.auto_rotate = auto_rotate;
     }
 }
 
-fn rasterize(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, v0: &Vector<FractionalType>, v1: &Vector<FractionalType>, v2: &Vector<FractionalType>, c: &Color)
-{
-    match categorize_triangle(v0, v1, v2)
+fn main() {
+    // the command line overrides the config file, e.g. --resolution=320x240,
+    // and sets the input speeds, e.g. --move_speed=4
+    let mut preferences = RenderPreferences::load_or_default(std::path::Path::new(preferences::DEFAULT_PATH));
+    let mut input_settings = InputSettings::default();
+    let args = match input_settings.apply_args(std::env::args().skip(1))
+        .and_then(|args| preferences.apply_args(args))
     {
-        TriangleType::FlatTop(flat_top) => rasterize_flat_top_triangle(canvas, &flat_top, c),
-        TriangleType::FlatBottom(flat_bottom) => rasterize_flat_bottom_triangle(canvas, &flat_bottom, c),
-        TriangleType::NonFlat(non_flat) => rasterize_non_flat_triangle(canvas, &non_flat, c),
-        TriangleType::NotATriangle => (),
-    }
-}
+        Ok(args) => args,
+        Err(error) =>
+        {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
 
-fn main() {
-    use rulinalg::matrix::Matrix;
     // model stuff
-    let teapot_path = std::path::Path::new("res/mdl/hello/hello.obj");
-    let teapot_mdl = obj::Obj::load(teapot_path).unwrap();
-
-    // object -> group -> polygon -> triangle
-    let mut teapot_triangle_colors: Vec<Vec<Vec<Vec<Color>>>> = Vec::new();
-    for object in &teapot_mdl.data.objects
+    // a model to view can be passed on the command line, otherwise the
+    // bundled ones are cycled through
+    let mut model_path = args.iter()
+        .find(|arg| !arg.starts_with("--"))
+        .cloned()
+        .unwrap_or_else(|| model::BUNDLED[0].to_string());
+    let (mut model, mut model_bounds) = match load_model(&model_path)
     {
-        teapot_triangle_colors.push(Vec::new());
-        let color_obj_idx = teapot_triangle_colors.len() - 1;
-        let color_obj_vec = &mut teapot_triangle_colors[color_obj_idx];
-        for group in &object.groups
+        Ok(loaded) => loaded,
+        Err(error) =>
         {
-            color_obj_vec.push(Vec::new());
-            let color_obj_grp_idx = color_obj_vec.len() - 1;
-            let color_obj_grp_vec = &mut color_obj_vec[color_obj_grp_idx];
-            for polygon in &group.polys
-            {
-                if polygon.0.len() < 3
-                {
-                    continue;
-                }
-                color_obj_grp_vec.push(Vec::new());
-                let color_obj_grp_poly_idx = color_obj_grp_vec.len() - 1;
-                let color_obj_grp_poly_vec = &mut color_obj_grp_vec[color_obj_grp_poly_idx];
-                for _ in 2..polygon.0.len()
-                {
-                    let random_color = 127u8 + (rand::random::<u8>() >> 2);
-                    color_obj_grp_poly_vec.push(Color::RGB(random_color, random_color, random_color));
-                }
-            }
+            eprintln!("{}: {}", model_path, error);
+            std::process::exit(1);
         }
-    }
-
-    let knight_path = std::path::Path::new("res/mdl/psx-knight/psx-knight.gltf");
-    let knight_gltf = gltf::Gltf::open(knight_path).unwrap();
+    };
+    let mut playback = Playback::new(&model);
+    // for models without textures, e.g. OBJ files whose materials we ignore
+    let fallback_texture = Texture::checkerboard(64, 8, Color::RGB(224, 224, 224), Color::RGB(160, 48, 48));
+    let light = DirectionalLight::default();
 
     // camera stuff
-    // https://www.cs.cmu.edu/~16385/s17/Slides/11.1_Camera_matrix.pdf
-    // P = K * R * [I | -C]
-    // where P is the camera projection, K is the 3x3 intrinsics, R is the 3D rotation matrix, -C is the negative 3D translation
-
-    // these variables are modified directly by user input
-    let mut camera_position: (f32, f32) = (0.0, -5.0); // x, z
-    let mut camera_facing: (f32, f32) = (0.0, 0.0); // left-right rotation (about y), upward rotation (about x)
-
-    // Intrinsic matrix K = (f 0 p_x)
-    //                      (0 f p_y)
-    //                      (0 0  1 )
-    // where f is the focal length, p is translation from camera coordinate system to image coordinate system
-    let camera_matrix_image_focal_length: Matrix<FractionalType> = rulinalg::matrix![
-        FractionalType::from_num(FOCAL_LENGTH), FractionalType::from_num(0),            FractionalType::from_num(0);
-        FractionalType::from_num(0),            FractionalType::from_num(FOCAL_LENGTH), FractionalType::from_num(0);
-        FractionalType::from_num(0),            FractionalType::from_num(0),            FractionalType::from_num(1)
-    ];
-    let camera_matrix_image_flip: Matrix<FractionalType> = rulinalg::matrix![
-        FractionalType::from_num(1), FractionalType::from_num(0),  FractionalType::from_num(0);
-        FractionalType::from_num(0), FractionalType::from_num(-1), FractionalType::from_num(0);
-        FractionalType::from_num(0), FractionalType::from_num(0),  FractionalType::from_num(1)
-    ];
-    let camera_matrix_image_translation: Matrix<FractionalType> = rulinalg::matrix![
-        FractionalType::from_num(1), FractionalType::from_num(0), FractionalType::from_num(1);
-        FractionalType::from_num(0), FractionalType::from_num(1), FractionalType::from_num(1);
-        FractionalType::from_num(0), FractionalType::from_num(0), FractionalType::from_num(1)
-    ];
-    let camera_matrix_image_scale: Matrix<FractionalType> = rulinalg::matrix![
-        FractionalType::from_num(RESOLUTION_X) / FractionalType::from_num(2), FractionalType::from_num(0),                                          FractionalType::from_num(0);
-        FractionalType::from_num(0),                                          FractionalType::from_num(RESOLUTION_Y) / FractionalType::from_num(2), FractionalType::from_num(0);
-        FractionalType::from_num(0),                                          FractionalType::from_num(0),                                          FractionalType::from_num(1)
-    ];
-    let camera_matrix_intrinsics = camera_matrix_image_scale * camera_matrix_image_translation * camera_matrix_image_flip * camera_matrix_image_focal_length;
+    let mut camera = Camera::new(Vec3::zero(), preferences.resolution);
+    camera.frame(&model_bounds.sphere);
+    let bindings = Bindings::load_or_default(std::path::Path::new(input::bindings::DEFAULT_PATH));
+    let mut take_screenshot = false;
+    let mut show_overlay = true;
+    // Some while the camera orbits the model, None while it flies freely
+    let mut orbit: Option<Orbit> = None;
 
     // sdl2 stuff
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
  
-    let window = video_subsystem.window("psx-renderer", 640, 480)
+    let (window_x, window_y) = display::default_window_size(preferences.resolution);
+    let window = video_subsystem.window("psx-renderer", window_x, window_y)
         .position_centered()
+        .resizable()
         .build()
         .unwrap();
 
-    let mut canvas = window.into_canvas().build().unwrap();
+    // pass --no-vsync to pace frames to the frame rate target, with vsync the
+    // display's refresh paces them
+    let vsync = !args.iter().any(|arg| arg == "--no-vsync");
+    let mut canvas_builder = window.into_canvas();
+    if vsync
+    {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let mut canvas = canvas_builder.build().unwrap();
+    // nearest neighbour scaling for the framebuffer texture
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+    let texture_creator = canvas.texture_creator();
+    let create_framebuffer_texture = |(width, height): (u32, u32)| texture_creator
+        .create_texture_streaming(sdl2::pixels::PixelFormatEnum::RGB24, width, height)
+        .unwrap();
+    let mut framebuffer = Framebuffer::new(preferences.resolution.0, preferences.resolution.1);
+    let mut framebuffer_texture = create_framebuffer_texture(preferences.resolution);
 
     canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0));
     canvas.clear();
     canvas.present();
     let mut event_pump = sdl_context.event_pump().unwrap();
+    let mouse = sdl_context.mouse();
+    // without a game controller subsystem the viewer still runs, just without
+    // pads
+    let controller_subsystem = sdl_context.game_controller().ok();
+    // the first pad plugged in drives the camera, it can also be connected later
+    let mut controller = controller_subsystem.as_ref().and_then(|subsystem| {
+        (0..subsystem.num_joysticks().unwrap_or(0))
+            .find(|&index| subsystem.is_game_controller(index))
+            .and_then(|index| subsystem.open(index).ok())
+    });
+    let mut clock = FrameClock::new(FrameRate::Ntsc60, vsync);
+    let mut field = Field::Odd;
     'running: loop {
-        canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0));
-        canvas.clear();
+        let steps = clock.begin_frame();
+        if (framebuffer.width(), framebuffer.height()) != preferences.resolution
+        {
+            let (width, height) = preferences.resolution;
+            framebuffer = Framebuffer::new(width, height);
+            framebuffer_texture = create_framebuffer_texture(preferences.resolution);
+            camera.set_resolution(preferences.resolution);
+            println!("resolution {}x{}", width, height);
+        }
+        // in 480i the display shows one field per frame; drawing to the
+        // display redraws only its lines, otherwise both fields are drawn
+        // like into a back buffer
+        let shown_field = preferences.is_interlaced().then(|| {
+            field = field.next();
+            field
+        });
+        framebuffer.set_field(shown_field.filter(|_| preferences.draw_to_display));
+        framebuffer.clear(Color::RGB(0, 0, 0));
 
         //////////////////
         //  USER INPUT  //
         //////////////////
         for event in event_pump.poll_iter() {
             match event {
-                sdl2::event::Event::Quit {..} |
-                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::Escape), .. } => {
+                sdl2::event::Event::Quit {..} => {
                     break 'running
                 },
-                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::W), .. } => {
-                    camera_position.1 = camera_position.1 + 0.1;
-                },
-                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::A), .. } => {
-                    camera_position.0 = camera_position.0 - 0.1;
-                },
-                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::S), .. } => {
-                    camera_position.1 = camera_position.1 - 0.1;
+                sdl2::event::Event::KeyDown { keycode: Some(keycode), repeat, .. } => {
+                    let action = match bindings.action(keycode)
+                    {
+                        // holding a key only repeats zooming and scrubbing,
+                        // toggles would flicker
+                        Some(action) if !repeat || matches!(
+                            action,
+                            Action::ZoomIn | Action::ZoomOut | Action::ScrubAnimationBack | Action::ScrubAnimationForward
+                        ) => action,
+                        _ => continue,
+                    };
+                    match action
+                    {
+                        Action::Quit => break 'running,
+                        Action::ToggleOrbit =>
+                        {
+                            orbit = match orbit
+                            {
+                                None => Some(Orbit::from_camera(&camera, model_bounds.aabb.center())),
+                                Some(_) => None,
+                            };
+                        }
+                        // the whole model for now
+                        Action::FrameSelection => frame_model(&mut camera, &mut orbit, &model_bounds),
+                        Action::NextModel =>
+                        {
+                            let index = model::BUNDLED.iter().position(|&path| path == model_path).map_or(0, |index| index + 1);
+                            let path = model::BUNDLED[index % model::BUNDLED.len()];
+                            match load_model(path)
+                            {
+                                Ok(loaded) =>
+                                {
+                                    (model, model_bounds) = loaded;
+                                    playback = Playback::new(&model);
+                                    model_path = path.to_string();
+                                    frame_model(&mut camera, &mut orbit, &model_bounds);
+                                    println!("model {}", model_path);
+                                }
+                                Err(error) => eprintln!("{}: {}", path, error),
+                            }
+                        }
+                        Action::ToggleAutoRotate =>
+                        {
+                            if let Some(orbit) = &mut orbit
+                            {
+                                orbit.auto_rotate = !orbit.auto_rotate;
+                            }
+                        }
+                        Action::ToggleMouseLook => mouse.set_relative_mouse_mode(!mouse.relative_mouse_mode()),
+                        Action::ToggleGte => camera.use_gte = !camera.use_gte,
+                        Action::ToggleDivideMode =>
+                        {
+                            camera.divide_mode = match camera.divide_mode
+                            {
+                                DivideMode::Exact => DivideMode::Hardware,
+                                DivideMode::Hardware => DivideMode::Exact,
+                            };
+                        }
+                        Action::ZoomIn => change_projection_zoom(&mut camera, PROJECTION_H_STEP),
+                        Action::ZoomOut => change_projection_zoom(&mut camera, -PROJECTION_H_STEP),
+                        Action::ToggleProjection =>
+                        {
+                            let mode = match camera.projection().mode
+                            {
+                                ProjectionMode::Perspective =>
+                                {
+                                    // keep the model, which sits around the origin, the same size
+                                    let mut projection = *camera.projection();
+                                    projection.match_scale_at(camera.depth(&Vec3::zero()));
+                                    camera.set_projection(projection);
+                                    ProjectionMode::Orthographic
+                                }
+                                ProjectionMode::Orthographic => ProjectionMode::Perspective,
+                            };
+                            camera.set_projection_mode(mode);
+                        }
+                        Action::CycleRenderMode => preferences.render_mode = preferences.render_mode.next(),
+                        Action::RenderPoints => preferences.render_mode = RenderMode::Points,
+                        Action::RenderWireframe => preferences.render_mode = RenderMode::Wireframe,
+                        Action::RenderFlat => preferences.render_mode = RenderMode::Flat,
+                        Action::RenderGouraud => preferences.render_mode = RenderMode::Gouraud,
+                        Action::RenderTextured => preferences.render_mode = RenderMode::Textured,
+                        Action::ToggleWireframe => preferences.wireframe_overlay = !preferences.wireframe_overlay,
+                        Action::ToggleVertices => preferences.vertex_overlay = !preferences.vertex_overlay,
+                        Action::ToggleDithering => preferences.dithering = !preferences.dithering,
+                        Action::ToggleVertexSnapping => preferences.vertex_snapping = !preferences.vertex_snapping,
+                        Action::CycleTextureMapping => preferences.texture_mapping = preferences.texture_mapping.next(),
+                        Action::CycleTextureFiltering => preferences.texture_filtering = preferences.texture_filtering.next(),
+                        Action::CycleCulling => preferences.culling = preferences.culling.next(),
+                        Action::CycleSortMode => preferences.sort_mode = preferences.sort_mode.next(),
+                        Action::CycleSkinning => preferences.skinning = preferences.skinning.next(),
+                        Action::NextAnimation => playback.next_animation(&model),
+                        Action::ToggleAnimation => playback.toggle_playing(&model),
+                        Action::ScrubAnimationBack => playback.scrub(&model, -1),
+                        Action::ScrubAnimationForward => playback.scrub(&model, 1),
+                        Action::ToggleAnimationLoop => playback.looping = !playback.looping,
+                        Action::SlowerAnimation => playback.change_speed(false),
+                        Action::FasterAnimation => playback.change_speed(true),
+                        Action::ToggleFog => preferences.fog = !preferences.fog,
+                        Action::CycleColorDepth => preferences.color_depth = preferences.color_depth.next(),
+                        Action::CycleHorizontalResolution =>
+                        {
+                            preferences.resolution.0 = display::next_width(preferences.resolution.0);
+                        }
+                        Action::ToggleVerticalResolution =>
+                        {
+                            preferences.resolution.1 = display::next_height(preferences.resolution.1);
+                        }
+                        Action::ToggleIntegerScaling => preferences.integer_scaling = !preferences.integer_scaling,
+                        Action::ToggleInterlacing => preferences.interlaced = !preferences.interlaced,
+                        Action::ToggleDrawToDisplay => preferences.draw_to_display = !preferences.draw_to_display,
+                        Action::CycleDeinterlace => preferences.deinterlace = preferences.deinterlace.next(),
+                        Action::SavePreferences =>
+                        {
+                            match preferences.save(std::path::Path::new(preferences::DEFAULT_PATH))
+                            {
+                                Ok(()) => println!("saved {}", preferences::DEFAULT_PATH),
+                                Err(error) => eprintln!("{}", error),
+                            }
+                        }
+                        Action::Screenshot => take_screenshot = true,
+                        Action::ToggleOverlay => show_overlay = !show_overlay,
+                        Action::CycleFrameRate =>
+                        {
+                            clock.set_rate(clock.rate().next());
+                            println!("target {}", clock.rate());
+                        }
+                        // held actions are polled below
+                        _ => {}
+                    }
                 },
-                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::D), .. } => {
-                    camera_position.0 = camera_position.0 + 0.1;
+                sdl2::event::Event::MouseMotion { xrel, yrel, .. } if orbit.is_none() && mouse.relative_mouse_mode() => {
+                    input::mouse_look(&mut camera, &input_settings, xrel, yrel);
                 },
-                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::O), .. } => {
-                    camera_facing.1 = camera_facing.1 + 0.1;
+                sdl2::event::Event::MouseMotion { mousestate, xrel, yrel, .. } if mousestate.left() => {
+                    if let Some(orbit) = &mut orbit
+                    {
+                        // dragging turns the model with the mouse
+                        orbit.orbit(-xrel * ORBIT_MOUSE_STEP, -yrel * ORBIT_MOUSE_STEP);
+                    }
                 },
-                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::K), .. } => {
-                    camera_facing.0 = camera_facing.0 + 0.1;
+                sdl2::event::Event::MouseWheel { y, .. } => {
+                    if let Some(orbit) = &mut orbit
+                    {
+                        orbit.zoom(y);
+                    }
                 },
-                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::L), .. } => {
-                    camera_facing.1 = camera_facing.1 - 0.1;
+                sdl2::event::Event::ControllerDeviceAdded { which, .. } if controller.is_none() => {
+                    controller = controller_subsystem.as_ref().and_then(|subsystem| subsystem.open(which).ok());
                 },
-                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::Semicolon), .. } => {
-                    camera_facing.0 = camera_facing.0 - 0.1;
+                sdl2::event::Event::ControllerDeviceRemoved { which, .. }
+                    if controller.as_ref().is_some_and(|controller| controller.instance_id() == which) => {
+                    controller = None;
                 },
                 _ => {}
             }
         }
 
-        ///////////////////////////
-        // UPDATE CAMERA MATRIX  //
-        ///////////////////////////
-        let camera_matrix_world_translation: Matrix<FractionalType> = rulinalg::matrix![
-            FractionalType::from_num(1), FractionalType::from_num(0), FractionalType::from_num(0), FractionalType::from_num(-camera_position.0);
-            FractionalType::from_num(0), FractionalType::from_num(1), FractionalType::from_num(0), FractionalType::from_num(0);
-            FractionalType::from_num(0), FractionalType::from_num(0), FractionalType::from_num(1), FractionalType::from_num(-camera_position.1)
-        ];
-        let camera_matrix_world_rotation_y: Matrix<FractionalType> = rulinalg::matrix![
-            FractionalType::from_num(camera_facing.0.cos()), FractionalType::from_num(0), FractionalType::from_num(camera_facing.0.sin());
-            FractionalType::from_num(0), FractionalType::from_num(1), FractionalType::from_num(0);
-            FractionalType::from_num(-camera_facing.0.sin()), FractionalType::from_num(0), FractionalType::from_num(camera_facing.0.cos())
-        ];
-        let camera_matrix_world_rotation_x: Matrix<FractionalType> = rulinalg::matrix![
-            FractionalType::from_num(1), FractionalType::from_num(0), FractionalType::from_num(0);
-            FractionalType::from_num(0), FractionalType::from_num(camera_facing.1.cos()), FractionalType::from_num(-camera_facing.1.sin());
-            FractionalType::from_num(0), FractionalType::from_num(camera_facing.1.sin()), FractionalType::from_num(camera_facing.1.cos())
-        ];
-        let camera_matrix = &camera_matrix_intrinsics * &camera_matrix_world_rotation_x* &camera_matrix_world_rotation_y * &camera_matrix_world_translation;
-
-        /////////////////////////////
-        // TRIANGLE RASTERIZATION  //
-        /////////////////////////////
-        for object in &teapot_mdl.data.objects
+        ////////////////
+        // SIMULATION //
+        ////////////////
+        // input is sampled once per frame and held for all of its steps
+        let mut motion = Motion::from_keyboard(&event_pump.keyboard_state(), &bindings);
+        if let Some(controller) = &controller
         {
-            for group in &object.groups
+            motion.add_controller(controller, &input_settings);
+        }
+        for _ in 0..steps
+        {
+            motion.apply(&mut camera, &input_settings, clock.step());
+            if let Some(orbit) = &mut orbit
             {
-                for polygon in &group.polys
-                {
-                    if polygon.0.len() < 3
-                    {
-                        continue;
-                    }
-                    // we're going to draw all the polygons by splitting it up into triangles
-                    // each triangle will use v_0 as a common vertex, then use vertex v_(i - 1) and 
-                    // v_i as the other two vertices. Before the loop, we just draw the line between v_0 and v_1
-                    // Each loop iteration, we draw two lines, one from v_0 to v_(i - 1) and one from v_0 to v_i
-                    let v0_idx = polygon.0[0].0;
-                    let v0_array = &teapot_mdl.data.position[v0_idx];
-                    let v0_world_homogeneous = rulinalg::vector![
-                        FractionalType::from_num(v0_array[0]),
-                        FractionalType::from_num(v0_array[1]),
-                        FractionalType::from_num(v0_array[2]),
-                        FractionalType::from_num(1)
-                    ];
-                    let v0_im_homogeneous = &camera_matrix * v0_world_homogeneous;
-                    let v0_im_frac = homogenous_image_coordinates_to_image_coordinates(&v0_im_homogeneous);
-
-
-                    let v1_idx = polygon.0[0].0;
-                    let v1_array = &teapot_mdl.data.position[v1_idx];
-                    let v1_world_homogeneous = rulinalg::vector![
-                        FractionalType::from_num(v1_array[0]),
-                        FractionalType::from_num(v1_array[1]),
-                        FractionalType::from_num(v1_array[2]),
-                        FractionalType::from_num(1)
-                    ];
-                    let v1_im_homogeneous = &camera_matrix * v1_world_homogeneous;
-                    let mut v1_im_frac = homogenous_image_coordinates_to_image_coordinates(&v1_im_homogeneous);
-
-                    for i in 2..polygon.0.len()
-                    {
-                        let color = Color::RGB(127, 127, 127);
-                        let v2_idx = polygon.0[i].0;
-                        let v2_array = &teapot_mdl.data.position[v2_idx];
-                        let v2_world_homogeneous = rulinalg::vector![
-                            FractionalType::from_num(v2_array[0]),
-                            FractionalType::from_num(v2_array[1]),
-                            FractionalType::from_num(v2_array[2]),
-                            FractionalType::from_num(1)
-                        ];
-                        let v2_im_homogeneous = &camera_matrix * v2_world_homogeneous;
-                        let v2_im_frac = homogenous_image_coordinates_to_image_coordinates(&v2_im_homogeneous);
-
-                        rasterize(&mut canvas, &v0_im_frac, &v1_im_frac, &v2_im_frac, &color);
-
-                        v1_im_frac = v2_im_frac;
-                    }
-                }
+                orbit.update();
             }
+            playback.update(&model, clock.step());
         }
-
-        ////////////////////
-        //  LINE DRAWING  //
-        ////////////////////
-        canvas.set_draw_color(sdl2::pixels::Color::RGB(191, 191, 191));
-        for object in &teapot_mdl.data.objects
+        model.set_pose(&playback);
+        // while orbiting the free-fly controls are overridden here
+        if let Some(orbit) = &orbit
         {
-            for group in &object.groups
-            {
-                for polygon in &group.polys
-                {
-                    if polygon.0.len() < 3
-                    {
-                        continue;
-                    }
-                    // we're going to draw all the polygons by splitting it up into triangles
-                    // each triangle will use v_0 as a common vertex, then use vertex v_(i - 1) and 
-                    // v_i as the other two vertices. Before the loop, we just draw the line between v_0 and v_1
-                    // Each loop iteration, we draw two lines, one from v_0 to v_(i - 1) and one from v_0 to v_i
-                    let v0_idx = polygon.0[0].0;
-                    let v0_array = &teapot_mdl.data.position[v0_idx];
-                    let v0_world_homogeneous = rulinalg::vector![
-                        FractionalType::from_num(v0_array[0]),
-                        FractionalType::from_num(v0_array[1]),
-                        FractionalType::from_num(v0_array[2]),
-                        FractionalType::from_num(1)
-                    ];
-                    let v0_im_homogeneous = &camera_matrix * v0_world_homogeneous;
-                    let v0_im_frac = homogenous_image_coordinates_to_image_coordinates(&v0_im_homogeneous);
-                    let v0_im: (i32, i32) = (v0_im_frac[0].to_num(), v0_im_frac[1].to_num());
-
-
-                    let v1_idx = polygon.0[0].0;
-                    let v1_array = &teapot_mdl.data.position[v1_idx];
-                    let v1_world_homogeneous = rulinalg::vector![
-                        FractionalType::from_num(v1_array[0]),
-                        FractionalType::from_num(v1_array[1]),
-                        FractionalType::from_num(v1_array[2]),
-                        FractionalType::from_num(1)
-                    ];
-                    let v1_im_homogeneous = &camera_matrix * v1_world_homogeneous;
-                    let v1_im_frac = homogenous_image_coordinates_to_image_coordinates(&v1_im_homogeneous);
-                    let mut v1_im: (i32, i32) = (v1_im_frac[0].to_num(), v1_im_frac[1].to_num());
-
-                    let _ = canvas.draw_line(v0_im, v1_im);
-
-                    for i in 2..polygon.0.len()
-                    {
-                        let v2_idx = polygon.0[i].0;
-                        let v2_array = &teapot_mdl.data.position[v2_idx];
-                        let v2_world_homogeneous = rulinalg::vector![
-                            FractionalType::from_num(v2_array[0]),
-                            FractionalType::from_num(v2_array[1]),
-                            FractionalType::from_num(v2_array[2]),
-                            FractionalType::from_num(1)
-                        ];
-                        let v2_im_homogeneous = &camera_matrix * v2_world_homogeneous;
-                        let v2_im_frac = homogenous_image_coordinates_to_image_coordinates(&v2_im_homogeneous);
-                        let v2_im: (i32, i32) = (v2_im_frac[0].to_num(), v2_im_frac[1].to_num());
-                        let _ = canvas.draw_line(v0_im, v2_im);
-                        let _ = canvas.draw_line(v1_im, v2_im);
+            orbit.apply(&mut camera);
+        }
 
-                        v1_im = v2_im;
-                    }
-                }
-            }
+        ///////////////
+        //  DRAWING  //
+        ///////////////
+        renderer::pipeline::draw_model(&mut framebuffer, &camera, &model, &fallback_texture, &light, &preferences);
+
+        ///////////////
+        //  OVERLAY  //
+        ///////////////
+        // the overlay isn't part of the emulated picture, it covers both fields
+        framebuffer.set_field(None);
+        if show_overlay
+        {
+            let lines = overlay_lines(&framebuffer, &camera, &clock, orbit.as_ref(), &preferences, &model, &playback);
+            overlay::draw(&mut framebuffer, &lines);
         }
 
-        //////////////////////
-        //  VERTEX DRAWING  //
-        //////////////////////
-        canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255));
-        for vertex_array in &teapot_mdl.data.position
+        if take_screenshot
         {
-            let vertex_vec4: Vector<FractionalType> = rulinalg::vector![
-                FractionalType::from_num(vertex_array[0]),
-                FractionalType::from_num(vertex_array[1]),
-                FractionalType::from_num(vertex_array[2]),
-                FractionalType::from_num(1)
-            ];
-            let vertex_vec3 = &camera_matrix * vertex_vec4;
-            if vertex_vec3[2] == FractionalType::from_num(0)
+            take_screenshot = false;
+            match save_screenshot(&framebuffer)
             {
-                continue;
+                Ok(path) => println!("saved {}", path),
+                Err(error) => eprintln!("screenshot failed: {}", error),
             }
-            let im_coords = (vertex_vec3[0] / vertex_vec3[2], vertex_vec3[1] / vertex_vec3[2]);
-            let (im_x, im_y): (i32, i32) = (im_coords.0.to_num(), im_coords.1.to_num());
-            if (im_x >= 0 && im_x < 640) &&
-                (im_y >= 0 && im_y < 480)
+        }
+
+        match shown_field
+        {
+            Some(field) if preferences.deinterlace == Deinterlace::Bob =>
             {
-                let _ = canvas.draw_point((im_x, im_y));
+                framebuffer_texture.update(None, &framebuffer.bob(field), framebuffer.pitch()).unwrap();
             }
+            _ => framebuffer_texture.update(None, framebuffer.pixels(), framebuffer.pitch()).unwrap(),
         }
-
+        let viewport = display::viewport(canvas.output_size().unwrap(), preferences.resolution, preferences.integer_scaling);
+        canvas.clear();
+        canvas.copy(&framebuffer_texture, None, viewport).unwrap();
+        clock.end_frame();
         canvas.present();
-        ::std::thread::sleep(std::time::Duration::new(0, 1_000_000_000u32 / 60));
     }
+
+    #[cfg(feature = "overflow-diagnostics")]
+    fractional_type::diagnostics::print_report();
 }
@@ -0,0 +1,916 @@
+use crate::fractional_type::{self, FractionalType};
+use num_traits::identities::{One, Zero};
+
+// Small fixed-size vector and matrix types over FractionalType. They are all
+// Copy and live on the stack, so transforming a vertex never allocates.
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Vec2
+{
+    pub x: FractionalType,
+    pub y: FractionalType,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Vec3
+{
+    pub x: FractionalType,
+    pub y: FractionalType,
+    pub z: FractionalType,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Vec4
+{
+    pub x: FractionalType,
+    pub y: FractionalType,
+    pub z: FractionalType,
+    pub w: FractionalType,
+}
+
+/// Row-major 3x3 matrix.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Mat3
+{
+    pub rows: [Vec3; 3],
+}
+
+/// Row-major 3x4 matrix, used both for affine transforms (a 3x3 part plus a
+/// translation column) and for camera projection matrices.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Mat3x4
+{
+    pub rows: [Vec4; 3],
+}
+
+/// Rotation quaternion w + xi + yj + zk. Rotations are only meaningful for
+/// unit quaternions, fixed point error makes them drift away from unit
+/// length, so renormalize after accumulating many products.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Quat
+{
+    pub w: FractionalType,
+    pub x: FractionalType,
+    pub y: FractionalType,
+    pub z: FractionalType,
+}
+
+////////////
+//  VEC2  //
+////////////
+
+impl Vec2
+{
+    pub fn new(x: FractionalType, y: FractionalType) -> Self
+    {
+        Self { x, y }
+    }
+}
+
+////////////
+//  VEC3  //
+////////////
+
+impl Vec3
+{
+    pub fn new(x: FractionalType, y: FractionalType, z: FractionalType) -> Self
+    {
+        Self { x, y, z }
+    }
+
+    pub fn zero() -> Self
+    {
+        Self::default()
+    }
+
+    pub fn from_array(array: [FractionalType; 3]) -> Self
+    {
+        Self::new(array[0], array[1], array[2])
+    }
+
+    pub fn to_array(self) -> [FractionalType; 3]
+    {
+        [self.x, self.y, self.z]
+    }
+
+    /// Converts model data such as OBJ positions, which come in as floats.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn from_f32(array: &[f32; 3]) -> Self
+    {
+        Self::new(FractionalType::from_num(array[0]), FractionalType::from_num(array[1]), FractionalType::from_num(array[2]))
+    }
+
+    pub fn extend(self, w: FractionalType) -> Vec4
+    {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn dot(self, other: Self) -> FractionalType
+    {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn cross(self, other: Self) -> Self
+    {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x
+        )
+    }
+
+    /// Overflows a 16 bit integer part for components past 181, see length.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn length_squared(self) -> FractionalType
+    {
+        self.dot(self)
+    }
+
+    // the largest absolute component
+    fn largest(self) -> FractionalType
+    {
+        self.x.abs().max(self.y.abs()).max(self.z.abs())
+    }
+
+    /// Scaled down by the largest component before squaring, so it neither
+    /// overflows past 181 nor underflows to zero for short vectors.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn length(self) -> FractionalType
+    {
+        let largest = self.largest();
+        if largest.is_zero()
+        {
+            largest
+        }
+        else
+        {
+            (self / largest).length_squared().sqrt() * largest
+        }
+    }
+
+    /// The unit vector in the same direction, or zero for a zero vector.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn normalize(self) -> Self
+    {
+        let largest = self.largest();
+        if largest.is_zero()
+        {
+            Self::zero()
+        }
+        else
+        {
+            let scaled = self / largest;
+            scaled / scaled.length_squared().sqrt()
+        }
+    }
+}
+
+impl std::ops::Index<usize> for Vec3
+{
+    type Output = FractionalType;
+
+    fn index(&self, index: usize) -> &FractionalType
+    {
+        match index
+        {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {}", index),
+        }
+    }
+}
+
+impl std::ops::Add for Vec3
+{
+    type Output = Self;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn add(self, other: Self) -> Self
+    {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl std::ops::AddAssign for Vec3
+{
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn add_assign(&mut self, other: Self)
+    {
+        *self = *self + other;
+    }
+}
+
+impl std::ops::Sub for Vec3
+{
+    type Output = Self;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn sub(self, other: Self) -> Self
+    {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl std::ops::SubAssign for Vec3
+{
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn sub_assign(&mut self, other: Self)
+    {
+        *self = *self - other;
+    }
+}
+
+impl std::ops::Neg for Vec3
+{
+    type Output = Self;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn neg(self) -> Self
+    {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl std::ops::Mul<FractionalType> for Vec3
+{
+    type Output = Self;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn mul(self, scalar: FractionalType) -> Self
+    {
+        Self::new(self.x * scalar, self.y * scalar, self.z * scalar)
+    }
+}
+
+impl std::ops::Div<FractionalType> for Vec3
+{
+    type Output = Self;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn div(self, scalar: FractionalType) -> Self
+    {
+        Self::new(self.x / scalar, self.y / scalar, self.z / scalar)
+    }
+}
+
+////////////
+//  VEC4  //
+////////////
+
+impl Vec4
+{
+    pub fn new(x: FractionalType, y: FractionalType, z: FractionalType, w: FractionalType) -> Self
+    {
+        Self { x, y, z, w }
+    }
+
+    pub fn truncate(self) -> Vec3
+    {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn dot(self, other: Self) -> FractionalType
+    {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+}
+
+////////////
+//  MAT3  //
+////////////
+
+impl Mat3
+{
+    pub fn from_rows(rows: [Vec3; 3]) -> Self
+    {
+        Self { rows }
+    }
+
+    pub fn from_array(array: [[FractionalType; 3]; 3]) -> Self
+    {
+        Self::from_rows(array.map(Vec3::from_array))
+    }
+
+    pub fn identity() -> Self
+    {
+        Self::diagonal(Vec3::new(FractionalType::one(), FractionalType::one(), FractionalType::one()))
+    }
+
+    pub fn diagonal(diagonal: Vec3) -> Self
+    {
+        let zero = FractionalType::zero();
+        Self::from_rows([
+            Vec3::new(diagonal.x, zero, zero),
+            Vec3::new(zero, diagonal.y, zero),
+            Vec3::new(zero, zero, diagonal.z),
+        ])
+    }
+
+    /// Rotation about the x axis, angle in fractional_type::ANGLE_ONE_TURN units.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn rotation_x(angle: i32) -> Self
+    {
+        let (sin, cos) = (fractional_type::sin(angle), fractional_type::cos(angle));
+        let (zero, one) = (FractionalType::zero(), FractionalType::one());
+        Self::from_rows([
+            Vec3::new(one, zero, zero),
+            Vec3::new(zero, cos, -sin),
+            Vec3::new(zero, sin, cos),
+        ])
+    }
+
+    /// Rotation about the y axis, angle in fractional_type::ANGLE_ONE_TURN units.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn rotation_y(angle: i32) -> Self
+    {
+        let (sin, cos) = (fractional_type::sin(angle), fractional_type::cos(angle));
+        let (zero, one) = (FractionalType::zero(), FractionalType::one());
+        Self::from_rows([
+            Vec3::new(cos, zero, sin),
+            Vec3::new(zero, one, zero),
+            Vec3::new(-sin, zero, cos),
+        ])
+    }
+
+    /// Rotation about the z axis, angle in fractional_type::ANGLE_ONE_TURN units.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn rotation_z(angle: i32) -> Self
+    {
+        let (sin, cos) = (fractional_type::sin(angle), fractional_type::cos(angle));
+        let (zero, one) = (FractionalType::zero(), FractionalType::one());
+        Self::from_rows([
+            Vec3::new(cos, -sin, zero),
+            Vec3::new(sin, cos, zero),
+            Vec3::new(zero, zero, one),
+        ])
+    }
+
+    pub fn col(&self, index: usize) -> Vec3
+    {
+        Vec3::new(self.rows[0][index], self.rows[1][index], self.rows[2][index])
+    }
+
+    pub fn transpose(&self) -> Self
+    {
+        Self::from_rows([self.col(0), self.col(1), self.col(2)])
+    }
+
+    /// The inverse of a pure rotation, which is just its transpose.
+    pub fn inverse_rotation(&self) -> Self
+    {
+        self.transpose()
+    }
+
+    /// Scales each row, e.g. to fold per-axis scale factors into a rotation.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn scale_rows(&self, scale: Vec3) -> Self
+    {
+        Self::from_rows([self.rows[0] * scale.x, self.rows[1] * scale.y, self.rows[2] * scale.z])
+    }
+
+    /// Negative if the matrix mirrors, i.e. flips the winding of triangles.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn determinant(&self) -> FractionalType
+    {
+        self.rows[0].dot(self.rows[1].cross(self.rows[2]))
+    }
+}
+
+impl std::ops::Mul<Vec3> for Mat3
+{
+    type Output = Vec3;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn mul(self, vector: Vec3) -> Vec3
+    {
+        Vec3::new(self.rows[0].dot(vector), self.rows[1].dot(vector), self.rows[2].dot(vector))
+    }
+}
+
+impl std::ops::Mul for Mat3
+{
+    type Output = Self;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn mul(self, other: Self) -> Self
+    {
+        // each row by the transpose rather than mapping the rows, a closure
+        // would hide the caller from #[track_caller]
+        let transpose = other.transpose();
+        Self::from_rows([transpose * self.rows[0], transpose * self.rows[1], transpose * self.rows[2]])
+    }
+}
+
+impl std::ops::Mul<Mat3x4> for Mat3
+{
+    type Output = Mat3x4;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn mul(self, other: Mat3x4) -> Mat3x4
+    {
+        Mat3x4::new(self * other.linear(), self * other.translation())
+    }
+}
+
+//////////////
+//  MAT3X4  //
+//////////////
+
+impl Mat3x4
+{
+    /// [linear | translation]
+    pub fn new(linear: Mat3, translation: Vec3) -> Self
+    {
+        Self {
+            rows: [
+                linear.rows[0].extend(translation.x),
+                linear.rows[1].extend(translation.y),
+                linear.rows[2].extend(translation.z),
+            ]
+        }
+    }
+
+    pub fn identity() -> Self
+    {
+        Self::new(Mat3::identity(), Vec3::zero())
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self
+    {
+        Self::new(Mat3::identity(), translation)
+    }
+
+    pub fn linear(&self) -> Mat3
+    {
+        Mat3::from_rows(self.rows.map(Vec4::truncate))
+    }
+
+    pub fn translation(&self) -> Vec3
+    {
+        Vec3::new(self.rows[0].w, self.rows[1].w, self.rows[2].w)
+    }
+
+    /// Transforms a point, i.e. a vector with an implicit w of one.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn transform_point(&self, point: Vec3) -> Vec3
+    {
+        self.linear() * point + self.translation()
+    }
+
+    /// Transforms a direction, which ignores the translation.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3
+    {
+        self.linear() * vector
+    }
+
+    /// The transform that applies `other` first and then `self`, treating
+    /// both as affine transforms with an implicit [0 0 0 1] bottom row.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn compose(&self, other: &Self) -> Self
+    {
+        Self::new(self.linear() * other.linear(), self.transform_point(other.translation()))
+    }
+
+    /// The inverse of a rigid transform (rotation and translation only).
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn inverse_rigid(&self) -> Self
+    {
+        let inverse_rotation = self.linear().inverse_rotation();
+        Self::new(inverse_rotation, -(inverse_rotation * self.translation()))
+    }
+}
+
+impl std::ops::Mul<Vec4> for Mat3x4
+{
+    type Output = Vec3;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn mul(self, vector: Vec4) -> Vec3
+    {
+        Vec3::new(self.rows[0].dot(vector), self.rows[1].dot(vector), self.rows[2].dot(vector))
+    }
+}
+
+impl std::ops::Mul for Mat3x4
+{
+    type Output = Self;
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn mul(self, other: Self) -> Self
+    {
+        self.compose(&other)
+    }
+}
+
+////////////
+//  QUAT  //
+////////////
+
+// below this angle between two rotations slerp is replaced by a normalized
+// lerp, where the sin ratio would lose all its precision
+const SLERP_MIN_ANGLE: i32 = 8;
+
+impl Quat
+{
+    pub fn new(w: FractionalType, x: FractionalType, y: FractionalType, z: FractionalType) -> Self
+    {
+        Self { w, x, y, z }
+    }
+
+    pub fn identity() -> Self
+    {
+        Self::from_scalar_vector(FractionalType::one(), Vec3::zero())
+    }
+
+    pub fn from_scalar_vector(w: FractionalType, vector: Vec3) -> Self
+    {
+        Self::new(w, vector.x, vector.y, vector.z)
+    }
+
+    /// Converts model data such as glTF rotations, which come in as floats in
+    /// x, y, z, w order.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn from_f32_xyzw(array: &[f32; 4]) -> Self
+    {
+        Self::new(
+            FractionalType::from_num(array[3]),
+            FractionalType::from_num(array[0]),
+            FractionalType::from_num(array[1]),
+            FractionalType::from_num(array[2])
+        )
+    }
+
+    /// Rotation by `angle` (in fractional_type::ANGLE_ONE_TURN units) about a
+    /// unit length `axis`.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn from_axis_angle(axis: Vec3, angle: i32) -> Self
+    {
+        let half_angle = FractionalType::from_num(angle) / FractionalType::from_num(2);
+        Self::from_scalar_vector(fractional_type::cos_fine(half_angle), axis * fractional_type::sin_fine(half_angle))
+    }
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn rotation_x(angle: i32) -> Self
+    {
+        Self::from_axis_angle(Vec3::new(FractionalType::one(), FractionalType::zero(), FractionalType::zero()), angle)
+    }
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn rotation_y(angle: i32) -> Self
+    {
+        Self::from_axis_angle(Vec3::new(FractionalType::zero(), FractionalType::one(), FractionalType::zero()), angle)
+    }
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn rotation_z(angle: i32) -> Self
+    {
+        Self::from_axis_angle(Vec3::new(FractionalType::zero(), FractionalType::zero(), FractionalType::one()), angle)
+    }
+
+    pub fn vector(self) -> Vec3
+    {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn dot(self, other: Self) -> FractionalType
+    {
+        self.w * other.w + self.vector().dot(other.vector())
+    }
+
+    /// The inverse rotation of a unit quaternion.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn conjugate(self) -> Self
+    {
+        Self::from_scalar_vector(self.w, -self.vector())
+    }
+
+    // every component divided by `divisor`
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn divided(self, divisor: FractionalType) -> Self
+    {
+        Self::new(self.w / divisor, self.x / divisor, self.y / divisor, self.z / divisor)
+    }
+
+    // the largest absolute component
+    fn largest(self) -> FractionalType
+    {
+        self.w.abs().max(self.vector().largest())
+    }
+
+    /// Scaled down by the largest component first, like Vec3::length.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn length(self) -> FractionalType
+    {
+        let largest = self.largest();
+        if largest.is_zero()
+        {
+            largest
+        }
+        else
+        {
+            let scaled = self.divided(largest);
+            scaled.dot(scaled).sqrt() * largest
+        }
+    }
+
+    /// The unit quaternion in the same direction, or the identity for a zero
+    /// quaternion.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn normalize(self) -> Self
+    {
+        let largest = self.largest();
+        if largest.is_zero()
+        {
+            Self::identity()
+        }
+        else
+        {
+            let scaled = self.divided(largest);
+            scaled.divided(scaled.dot(scaled).sqrt())
+        }
+    }
+
+    /// Rotates a vector, v' = q v q*.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn rotate(self, vector: Vec3) -> Vec3
+    {
+        // expanded form of q v q* that skips the products with v's zero w
+        let u = self.vector();
+        let t = u.cross(vector) * FractionalType::from_num(2);
+        vector + t * self.w + u.cross(t)
+    }
+
+    /// Spherical linear interpolation, `t` from zero (self) to one (other),
+    /// along the shorter arc.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn slerp(self, other: Self, t: FractionalType) -> Self
+    {
+        // q and -q are the same rotation, flip one to take the shorter way
+        let mut dot = self.dot(other);
+        let other = if dot < FractionalType::zero()
+        {
+            dot = -dot;
+            Self::new(-other.w, -other.x, -other.y, -other.z)
+        }
+        else
+        {
+            other
+        };
+
+        let one = FractionalType::one();
+        let sin_angle = (one - (dot * dot).min(one)).sqrt();
+        let angle = fractional_type::atan2_fine(sin_angle, dot);
+        let (a, b) = if angle < FractionalType::from_num(SLERP_MIN_ANGLE)
+        {
+            (one - t, t)
+        }
+        else
+        {
+            (
+                fractional_type::sin_fine(angle * (one - t)) / sin_angle,
+                fractional_type::sin_fine(angle * t) / sin_angle
+            )
+        };
+        Self::new(
+            self.w * a + other.w * b,
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b
+        ).normalize()
+    }
+
+    /// The rotation matrix of a unit quaternion.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    pub fn to_mat3(self) -> Mat3
+    {
+        let one = FractionalType::one();
+        let two = FractionalType::from_num(2);
+        let (w, x, y, z) = (self.w, self.x, self.y, self.z);
+        Mat3::from_rows([
+            Vec3::new(one - two * (y * y + z * z), two * (x * y - w * z), two * (x * z + w * y)),
+            Vec3::new(two * (x * y + w * z), one - two * (x * x + z * z), two * (y * z - w * x)),
+            Vec3::new(two * (x * z - w * y), two * (y * z + w * x), one - two * (x * x + y * y)),
+        ])
+    }
+}
+
+impl Default for Quat
+{
+    fn default() -> Self
+    {
+        Self::identity()
+    }
+}
+
+impl std::ops::Mul for Quat
+{
+    type Output = Self;
+
+    /// Hamilton product, the rotation that applies `other` first and then
+    /// `self`.
+    #[cfg_attr(feature = "overflow-diagnostics", track_caller)]
+    fn mul(self, other: Self) -> Self
+    {
+        let (a, b) = (self.vector(), other.vector());
+        Self::from_scalar_vector(
+            self.w * other.w - a.dot(b),
+            b * self.w + a * other.w + a.cross(b)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    // fixed point error accumulates, this is plenty for the 12 fraction bits
+    // of the coarsest representation
+    const EPSILON: f64 = 1.0 / 256.0;
+
+    fn num(value: f64) -> FractionalType
+    {
+        FractionalType::from_num(value)
+    }
+
+    fn vec3(x: f64, y: f64, z: f64) -> Vec3
+    {
+        Vec3::new(num(x), num(y), num(z))
+    }
+
+    fn assert_close(a: FractionalType, b: f64)
+    {
+        let a: f64 = a.to_num();
+        assert!((a - b).abs() <= EPSILON * b.abs().max(1.0), "{} is not {}", a, b);
+    }
+
+    fn assert_vec3_close(a: Vec3, b: Vec3)
+    {
+        for i in 0..3
+        {
+            assert_close(a[i], b[i].to_num());
+        }
+    }
+
+    fn assert_mat3_close(a: Mat3, b: Mat3)
+    {
+        for (a, b) in a.rows.iter().zip(&b.rows)
+        {
+            assert_vec3_close(*a, *b);
+        }
+    }
+
+    #[test]
+    fn length_of_short_and_long_vectors()
+    {
+        assert_close(vec3(3.0, 4.0, 0.0).length(), 5.0);
+        assert_close(vec3(-2.0, 3.0, -6.0).length(), 7.0);
+        // the squares would overflow a 16 bit integer part
+        assert_close(vec3(300.0, -400.0, 1200.0).length(), 1300.0);
+        // and underflow the fraction here
+        assert_close(vec3(3.0 / 256.0, 4.0 / 256.0, 0.0).length() * num(256.0), 5.0);
+        assert_eq!(Vec3::zero().length(), FractionalType::zero());
+    }
+
+    #[test]
+    fn normalize_gives_unit_vectors()
+    {
+        for vector in [vec3(1.0, 2.0, 3.0), vec3(-500.0, 20.0, 0.5), vec3(0.002, -0.001, 0.002), vec3(0.0, 0.0, -7.0)]
+        {
+            let unit = vector.normalize();
+            assert_close(unit.length(), 1.0);
+            // same direction
+            assert_close(unit.dot(vector), vector.length().to_num());
+        }
+        assert_eq!(Vec3::zero().normalize(), Vec3::zero());
+    }
+
+    #[test]
+    fn cross_follows_the_right_hand_rule()
+    {
+        let (x, y, z) = (vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0));
+        assert_eq!(x.cross(y), z);
+        assert_eq!(y.cross(z), x);
+        assert_eq!(y.cross(x), -z);
+        let (a, b) = (vec3(1.0, 2.0, 3.0), vec3(-2.0, 0.5, 4.0));
+        assert_eq!(a.cross(b).dot(a), FractionalType::zero());
+        assert_eq!(a.cross(b).dot(b), FractionalType::zero());
+    }
+
+    #[test]
+    fn determinant_of_rotations_scales_and_mirrors()
+    {
+        assert_close(Mat3::identity().determinant(), 1.0);
+        assert_close(Mat3::rotation_y(300).determinant(), 1.0);
+        assert_close((Mat3::rotation_x(-700) * Mat3::rotation_z(1234)).determinant(), 1.0);
+        assert_close(Mat3::diagonal(vec3(2.0, 3.0, 0.5)).determinant(), 3.0);
+        assert_close(Mat3::diagonal(vec3(-1.0, 1.0, 1.0)).determinant(), -1.0);
+    }
+
+    #[test]
+    fn inverse_rotation_round_trips()
+    {
+        let rotation = Mat3::rotation_x(512) * Mat3::rotation_y(-300) * Mat3::rotation_z(77);
+        assert_mat3_close(rotation * rotation.inverse_rotation(), Mat3::identity());
+        let vector = vec3(1.5, -2.0, 0.25);
+        assert_vec3_close(rotation.inverse_rotation() * (rotation * vector), vector);
+    }
+
+    #[test]
+    fn mat3_product_with_identity()
+    {
+        let matrix = Mat3::from_array([[1, 2, 3], [4, 5, 6], [7, 8, 10]].map(|row| row.map(FractionalType::from_num)));
+        assert_eq!(matrix * Mat3::identity(), matrix);
+        assert_eq!(Mat3::identity() * matrix, matrix);
+        // row i of a product is row i of the left times the right
+        let product = matrix * Mat3::rotation_z(fractional_type::ANGLE_QUARTER_TURN);
+        assert_vec3_close(product.rows[0], vec3(2.0, -1.0, 3.0));
+    }
+
+    #[test]
+    fn rigid_transforms_invert_and_compose()
+    {
+        let transform = Mat3x4::new(Mat3::rotation_y(900) * Mat3::rotation_x(-200), vec3(3.0, -1.0, 10.0));
+        let point = vec3(0.5, 2.0, -4.0);
+        let moved = transform.transform_point(point);
+        assert_vec3_close(transform.inverse_rigid().transform_point(moved), point);
+        assert_mat3_close(transform.compose(&transform.inverse_rigid()).linear(), Mat3::identity());
+        assert_vec3_close(transform.compose(&transform.inverse_rigid()).translation(), Vec3::zero());
+
+        let other = Mat3x4::new(Mat3::diagonal(vec3(2.0, 2.0, 2.0)), vec3(0.0, 1.0, 0.0));
+        assert_vec3_close(transform.compose(&other).transform_point(point), transform.transform_point(other.transform_point(point)));
+        // translations are ignored for directions
+        assert_vec3_close(transform.transform_vector(point), transform.linear() * point);
+    }
+
+    fn assert_quat_close(a: Quat, b: Quat)
+    {
+        // q and -q are the same rotation
+        let b = if a.dot(b) < FractionalType::zero() { Quat::new(-b.w, -b.x, -b.y, -b.z) } else { b };
+        for (a, b) in [(a.w, b.w), (a.x, b.x), (a.y, b.y), (a.z, b.z)]
+        {
+            assert_close(a, b.to_num());
+        }
+    }
+
+    #[test]
+    fn quat_to_mat3_matches_the_matrix_rotations()
+    {
+        for angle in [0, 100, 1024, -1500, 2048, 3000]
+        {
+            assert_mat3_close(Quat::rotation_x(angle).to_mat3(), Mat3::rotation_x(angle));
+            assert_mat3_close(Quat::rotation_y(angle).to_mat3(), Mat3::rotation_y(angle));
+            assert_mat3_close(Quat::rotation_z(angle).to_mat3(), Mat3::rotation_z(angle));
+        }
+        assert_eq!(Quat::identity().to_mat3(), Mat3::identity());
+    }
+
+    #[test]
+    fn quat_products_compose_like_matrices()
+    {
+        let (a, b) = (Quat::rotation_y(700), Quat::rotation_x(-250) * Quat::rotation_z(90));
+        assert_mat3_close((a * b).to_mat3(), a.to_mat3() * b.to_mat3());
+        let vector = vec3(1.0, -2.0, 0.5);
+        assert_vec3_close((a * b).rotate(vector), a.rotate(b.rotate(vector)));
+        assert_vec3_close(a.rotate(vector), a.to_mat3() * vector);
+        assert_quat_close(a * a.conjugate(), Quat::identity());
+    }
+
+    #[test]
+    fn quat_normalize_and_length()
+    {
+        let q = Quat::new(num(1.0), num(2.0), num(-2.0), num(4.0));
+        assert_close(q.length(), 5.0);
+        assert_close(q.normalize().length(), 1.0);
+        assert_close(Quat::new(num(300.0), num(0.0), num(400.0), num(0.0)).length(), 500.0);
+        assert_eq!(Quat::new(num(0.0), num(0.0), num(0.0), num(0.0)).normalize(), Quat::identity());
+    }
+
+    #[test]
+    fn slerp_endpoints_and_midpoint()
+    {
+        let (a, b) = (Quat::rotation_y(-400), Quat::rotation_y(1200));
+        assert_quat_close(a.slerp(b, num(0.0)), a);
+        assert_quat_close(a.slerp(b, num(1.0)), b);
+        assert_quat_close(a.slerp(b, num(0.5)), Quat::rotation_y(400));
+        assert_quat_close(a.slerp(b, num(0.25)), Quat::rotation_y(0));
+        // nearby rotations take the normalized lerp
+        assert_quat_close(a.slerp(Quat::rotation_y(-396), num(0.5)), Quat::rotation_y(-398));
+    }
+
+    #[test]
+    fn slerp_takes_the_shorter_arc()
+    {
+        // 3/4 turn one way is 1/4 the other, -q is the same rotation as q
+        let (a, b) = (Quat::rotation_z(0), Quat::rotation_z(3072));
+        assert_quat_close(a.slerp(b, num(0.5)), Quat::rotation_z(-512));
+        let negated = Quat::new(-b.w, -b.x, -b.y, -b.z);
+        assert_quat_close(a.slerp(negated, num(0.5)), Quat::rotation_z(-512));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::bounds::Bounds;
+use crate::fractional_type::FractionalType;
+use crate::math::{Mat3, Mat3x4, Quat, Vec3};
+use animation::{Animation, Playback};
+use num_traits::identities::One;
+use skin::Skin;
+use crate::renderer::texture::Texture;
+use crate::renderer::types::Mesh;
+
+pub mod animation;
+pub mod gltf;
+pub mod skin;
+
+// A model file loaded into the renderer's own types: its meshes, the textures
+// they refer to, the node hierarchy that places the meshes in the scene, the
+// skins deforming them and the animations moving the nodes. OBJ and glTF
+// files are supported.
+
+/// The models in res/, in the order the viewer cycles through them.
+pub const BUNDLED: &[&str] = &[
+    "res/mdl/hello/hello.obj",
+    "res/mdl/psx-knight/psx-knight.gltf",
+    "res/mdl/mercedes-benz-190/mercedes-benz-190.gltf",
+    "res/mdl/teapot/teapot.obj",
+    "res/mdl/monkey/monkey.obj",
+];
+
+#[derive(Debug)]
+pub enum ModelError
+{
+    Obj(obj::ObjError),
+    Gltf(::gltf::Error),
+    Unsupported(String),
+}
+
+impl std::fmt::Display for ModelError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            ModelError::Obj(error) => write!(f, "can't load OBJ: {}", error),
+            ModelError::Gltf(error) => write!(f, "can't load glTF: {}", error),
+            ModelError::Unsupported(what) => write!(f, "unsupported {}", what),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+impl From<obj::ObjError> for ModelError
+{
+    fn from(error: obj::ObjError) -> Self
+    {
+        ModelError::Obj(error)
+    }
+}
+
+impl From<::gltf::Error> for ModelError
+{
+    fn from(error: ::gltf::Error) -> Self
+    {
+        ModelError::Gltf(error)
+    }
+}
+
+/// A node's transform relative to its parent, applied as scale, then
+/// rotation, then translation like in glTF.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Transform
+{
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform
+{
+    pub fn identity() -> Self
+    {
+        let one = FractionalType::one();
+        Self { translation: Vec3::zero(), rotation: Quat::identity(), scale: Vec3::new(one, one, one) }
+    }
+
+    pub fn to_matrix(&self) -> Mat3x4
+    {
+        Mat3x4::new(self.rotation.to_mat3() * Mat3::diagonal(self.scale), self.translation)
+    }
+}
+
+impl Default for Transform
+{
+    fn default() -> Self
+    {
+        Self::identity()
+    }
+}
+
+/// A node of the scene graph, drawing its meshes (if any) with its world
+/// transform, i.e. its own transform composed with all of its ancestors'.
+#[derive(Clone, Default, Debug)]
+pub struct Node
+{
+    pub name: Option<String>,
+    /// the current pose, see Model::set_pose
+    pub transform: Transform,
+    /// the transform the file gives the node, which animations start from
+    pub rest_transform: Transform,
+    /// indices into Model::nodes
+    pub children: Vec<usize>,
+    /// indices into Model::meshes
+    pub meshes: Vec<usize>,
+    /// index into Model::skins, the skin deforming the meshes, whose
+    /// vertices are then placed by its joints instead of the node
+    pub skin: Option<usize>,
+}
+
+/// A mesh placed in the scene by a node.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Instance
+{
+    /// index into Model::meshes
+    pub mesh: usize,
+    /// from the mesh's space to world space, the identity for skinned
+    /// meshes
+    pub transform: Mat3x4,
+    /// index into Model::skins if the mesh is skinned
+    pub skin: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct Model
+{
+    pub meshes: Vec<Mesh>,
+    pub textures: Vec<Texture>,
+    pub nodes: Vec<Node>,
+    /// the nodes of the scene shown, indices into `nodes`
+    pub roots: Vec<usize>,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+}
+
+impl Model
+{
+    /// Loads an OBJ or glTF file, going by its extension.
+    pub fn load(path: &std::path::Path) -> Result<Self, ModelError>
+    {
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match extension.as_str()
+        {
+            "obj" => Self::from_obj(path),
+            "gltf" | "glb" => gltf::load(path),
+            _ => Err(ModelError::Unsupported(format!("model format `{}`", path.display()))),
+        }
+    }
+
+    /// Loads an OBJ file as a single untextured mesh. Materials are ignored.
+    pub fn from_obj(path: &std::path::Path) -> Result<Self, ModelError>
+    {
+        let obj = obj::Obj::load(path)?;
+        Ok(Self::from_meshes(vec![Mesh::from_obj(&obj.data)], Vec::new()))
+    }
+
+    /// A model with one untransformed node drawing all of `meshes`.
+    pub fn from_meshes(meshes: Vec<Mesh>, textures: Vec<Texture>) -> Self
+    {
+        let root = Node { meshes: (0..meshes.len()).collect(), ..Node::default() };
+        Self { meshes, textures, nodes: vec![root], roots: vec![0], skins: Vec::new(), animations: Vec::new() }
+    }
+
+    /// Poses the nodes where `playback` is, or at rest without an animation.
+    pub fn set_pose(&mut self, playback: &Playback)
+    {
+        for node in &mut self.nodes
+        {
+            node.transform = node.rest_transform;
+        }
+        if let Some(animation) = playback.animation.and_then(|index| self.animations.get(index))
+        {
+            animation.apply(playback.time, &mut self.nodes);
+        }
+    }
+
+    /// The world transform of every node, indexed like `nodes`. Nodes that
+    /// aren't part of the scene keep the identity.
+    pub fn world_transforms(&self) -> Vec<Mat3x4>
+    {
+        let mut transforms = vec![Mat3x4::identity(); self.nodes.len()];
+        // depth first, parents are always done before their children
+        let mut pending: Vec<(usize, Mat3x4)> = self.roots.iter()
+            .map(|&root| (root, Mat3x4::identity()))
+            .collect();
+        while let Some((index, parent)) = pending.pop()
+        {
+            let node = &self.nodes[index];
+            let transform = parent.compose(&node.transform.to_matrix());
+            transforms[index] = transform;
+            pending.extend(node.children.iter().map(|&child| (child, transform)));
+        }
+        transforms
+    }
+
+    /// Every mesh of the scene with the transform it's drawn with.
+    pub fn instances(&self) -> Vec<Instance>
+    {
+        let transforms = self.world_transforms();
+        let mut instances = Vec::new();
+        let mut pending: Vec<usize> = self.roots.iter().rev().copied().collect();
+        while let Some(index) = pending.pop()
+        {
+            let node = &self.nodes[index];
+            instances.extend(node.meshes.iter().map(|&mesh| {
+                // skinned meshes ignore the node's transform, their joints
+                // place them instead
+                let skin = node.skin.filter(|_| !self.meshes[mesh].joint_weights.is_empty());
+                let transform = if skin.is_some() { Mat3x4::identity() } else { transforms[index] };
+                Instance { mesh, transform, skin }
+            }));
+            pending.extend(node.children.iter().rev());
+        }
+        instances
+    }
+
+    /// The parent of every node, indexed like `nodes`.
+    pub fn parents(&self) -> Vec<Option<usize>>
+    {
+        let mut parents = vec![None; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate()
+        {
+            for &child in &node.children
+            {
+                parents[child] = Some(index);
+            }
+        }
+        parents
+    }
+
+    /// The joint matrices of every skin in the current pose, indexed like
+    /// `skins`.
+    pub fn joint_matrices(&self) -> Vec<Vec<Mat3x4>>
+    {
+        let local_transforms: Vec<Mat3x4> = self.nodes.iter()
+            .map(|node| node.transform.to_matrix())
+            .collect();
+        let parents = self.parents();
+        self.skins.iter()
+            .map(|skin| skin.joint_matrices(&local_transforms, &parents))
+            .collect()
+    }
+
+    /// The bounds of all mesh instances in bind pose, or None if the scene is
+    /// empty.
+    pub fn bounds(&self) -> Option<Bounds>
+    {
+        let positions: Vec<Vec3> = self.instances().iter()
+            .flat_map(|instance| self.meshes[instance.mesh].positions().map(|&position| instance.transform.transform_point(position)))
+            .collect();
+        Bounds::from_points(&positions)
+    }
+
+    /// The texture a mesh of this model uses, if any.
+    pub fn texture(&self, mesh: &Mesh) -> Option<&Texture>
+    {
+        mesh.texture.and_then(|index| self.textures.get(index))
+    }
+}
@@ -0,0 +1,382 @@
+use super::{Model, Node};
+use crate::fractional_type::FractionalType;
+use crate::math::{Quat, Vec3};
+use num_traits::identities::{One, Zero};
+
+// Keyframe animation of node transforms, as glTF stores it. Every channel
+// moves one property of one node, sampled at the playback time with the
+// channel's interpolation. Animating skeleton joints poses the meshes they
+// skin, so this is all skeletal animation needs.
+
+// scrubbing moves by this many seconds per key press (or key repeat)
+const SCRUB_STEP: f32 = 1.0 / 15.0;
+// the playback speed is halved or doubled within these
+const MIN_SPEED: f32 = 1.0 / 8.0;
+const MAX_SPEED: f32 = 4.0;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Interpolation
+{
+    /// holds every keyframe until the next one
+    Step,
+    /// straight lines between keyframes, rotations along the shorter arc
+    Linear,
+    /// Hermite splines through the keyframes, with in and out tangents per
+    /// keyframe
+    CubicSpline,
+}
+
+/// Keyframe values of the node property a channel animates. For cubic
+/// splines there are three per keyframe: in-tangent, value, out-tangent.
+#[derive(Clone, Debug)]
+pub enum Values
+{
+    Translations(Vec<Vec3>),
+    Rotations(Vec<Quat>),
+    Scales(Vec<Vec3>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Channel
+{
+    /// index into Model::nodes
+    pub node: usize,
+    pub interpolation: Interpolation,
+    /// keyframe times in seconds, increasing
+    pub times: Vec<FractionalType>,
+    pub values: Values,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Animation
+{
+    pub name: Option<String>,
+    pub channels: Vec<Channel>,
+    /// the time of the last keyframe, in seconds
+    pub duration: FractionalType,
+}
+
+impl Animation
+{
+    /// Sets the animated properties of `nodes` to their value at `time`,
+    /// leaving the rest alone.
+    pub fn apply(&self, time: FractionalType, nodes: &mut [Node])
+    {
+        for channel in &self.channels
+        {
+            let transform = &mut nodes[channel.node].transform;
+            match &channel.values
+            {
+                Values::Translations(values) => transform.translation = sample(&channel.times, values, channel.interpolation, time),
+                Values::Rotations(values) => transform.rotation = sample(&channel.times, values, channel.interpolation, time),
+                Values::Scales(values) => transform.scale = sample(&channel.times, values, channel.interpolation, time),
+            }
+        }
+    }
+}
+
+/// Which animation of a model plays, and where it is.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Playback
+{
+    /// index into Model::animations, None shows the rest pose
+    pub animation: Option<usize>,
+    /// seconds into the animation
+    pub time: FractionalType,
+    pub playing: bool,
+    /// start over at the end instead of stopping
+    pub looping: bool,
+    pub speed: FractionalType,
+}
+
+impl Playback
+{
+    /// Plays the model's first animation, if it has any, in a loop.
+    pub fn new(model: &Model) -> Self
+    {
+        Self {
+            animation: (!model.animations.is_empty()).then_some(0),
+            time: FractionalType::zero(),
+            playing: true,
+            looping: true,
+            speed: FractionalType::one(),
+        }
+    }
+
+    /// The next animation from the start, after the last one the rest pose.
+    pub fn next_animation(&mut self, model: &Model)
+    {
+        self.animation = match self.animation
+        {
+            None if !model.animations.is_empty() => Some(0),
+            Some(index) if index + 1 < model.animations.len() => Some(index + 1),
+            _ => None,
+        };
+        self.time = FractionalType::zero();
+    }
+
+    /// Moves on by `seconds` of real time if playing.
+    pub fn update(&mut self, model: &Model, seconds: FractionalType)
+    {
+        if self.playing
+        {
+            self.seek(model, self.time + seconds * self.speed);
+        }
+    }
+
+    /// Pauses and steps forward (or back for negative steps) by SCRUB_STEP.
+    pub fn scrub(&mut self, model: &Model, steps: i32)
+    {
+        self.playing = false;
+        self.seek(model, self.time + FractionalType::from_num(SCRUB_STEP) * FractionalType::from_num(steps));
+    }
+
+    /// Plays or pauses, a finished animation starts over.
+    pub fn toggle_playing(&mut self, model: &Model)
+    {
+        self.playing = !self.playing;
+        if self.playing && !self.looping && self.time >= self.duration(model)
+        {
+            self.time = FractionalType::zero();
+        }
+    }
+
+    /// Doubles the speed, or halves it if not `faster`.
+    pub fn change_speed(&mut self, faster: bool)
+    {
+        let two = FractionalType::from_num(2);
+        let speed = if faster { self.speed * two } else { self.speed / two };
+        self.speed = speed.clamp(FractionalType::from_num(MIN_SPEED), FractionalType::from_num(MAX_SPEED));
+    }
+
+    pub fn duration(&self, model: &Model) -> FractionalType
+    {
+        self.animation
+            .and_then(|index| model.animations.get(index))
+            .map_or(FractionalType::zero(), |animation| animation.duration)
+    }
+
+    // wraps around when looping and stops at either end otherwise
+    fn seek(&mut self, model: &Model, time: FractionalType)
+    {
+        let duration = self.duration(model);
+        if duration <= FractionalType::zero()
+        {
+            self.time = FractionalType::zero();
+        }
+        else if self.looping
+        {
+            self.time = time - (time / duration).floor() * duration;
+        }
+        else
+        {
+            self.time = time.clamp(FractionalType::zero(), duration);
+            if time >= duration
+            {
+                self.playing = false;
+            }
+        }
+    }
+}
+
+// values keyframes can interpolate between
+trait Keyframe: Copy
+{
+    fn lerp(self, other: Self, t: FractionalType) -> Self;
+
+    // the cubic Hermite spline from `self` to `other`, `weights` from
+    // hermite_weights
+    fn hermite(self, out_tangent: Self, other: Self, in_tangent: Self, weights: [FractionalType; 4]) -> Self;
+}
+
+impl Keyframe for Vec3
+{
+    fn lerp(self, other: Self, t: FractionalType) -> Self
+    {
+        self + (other - self) * t
+    }
+
+    fn hermite(self, out_tangent: Self, other: Self, in_tangent: Self, weights: [FractionalType; 4]) -> Self
+    {
+        let [a, b, c, d] = weights;
+        self * a + out_tangent * b + other * c + in_tangent * d
+    }
+}
+
+impl Keyframe for Quat
+{
+    fn lerp(self, other: Self, t: FractionalType) -> Self
+    {
+        self.slerp(other, t)
+    }
+
+    // per component, then back to unit length like glTF asks for
+    fn hermite(self, out_tangent: Self, other: Self, in_tangent: Self, weights: [FractionalType; 4]) -> Self
+    {
+        let [a, b, c, d] = weights;
+        let component = |component: fn(&Quat) -> FractionalType| {
+            component(&self) * a + component(&out_tangent) * b + component(&other) * c + component(&in_tangent) * d
+        };
+        Quat::new(component(|q| q.w), component(|q| q.x), component(|q| q.y), component(|q| q.z)).normalize()
+    }
+}
+
+// the weights of the start value, out tangent, end value and in tangent at
+// `t` from zero to one, glTF tangents are per second so theirs are scaled to
+// the `span` of seconds between the keyframes
+fn hermite_weights(t: FractionalType, span: FractionalType) -> [FractionalType; 4]
+{
+    let (two, three) = (FractionalType::from_num(2), FractionalType::from_num(3));
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        two * t3 - three * t2 + FractionalType::one(),
+        (t3 - two * t2 + t) * span,
+        three * t2 - two * t3,
+        (t3 - t2) * span,
+    ]
+}
+
+// the channel's value at `time`, holding the first and last keyframes before
+// and after the animation
+fn sample<T: Keyframe>(times: &[FractionalType], values: &[T], interpolation: Interpolation, time: FractionalType) -> T
+{
+    let cubic = interpolation == Interpolation::CubicSpline;
+    let value = |keyframe: usize| if cubic { values[3 * keyframe + 1] } else { values[keyframe] };
+    let next = times.partition_point(|&keyframe_time| keyframe_time <= time);
+    if next == 0
+    {
+        return value(0);
+    }
+    let previous = next - 1;
+    if next == times.len() || times[next] <= times[previous]
+    {
+        return value(previous);
+    }
+    let span = times[next] - times[previous];
+    let t = (time - times[previous]) / span;
+    match interpolation
+    {
+        Interpolation::Step => value(previous),
+        Interpolation::Linear => value(previous).lerp(value(next), t),
+        Interpolation::CubicSpline => value(previous).hermite(
+            values[3 * previous + 2],
+            value(next),
+            values[3 * next],
+            hermite_weights(t, span)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn num(value: f64) -> FractionalType
+    {
+        FractionalType::from_num(value)
+    }
+
+    fn times(times: &[f64]) -> Vec<FractionalType>
+    {
+        times.iter().copied().map(num).collect()
+    }
+
+    fn x(x: f64) -> Vec3
+    {
+        Vec3::new(num(x), FractionalType::zero(), FractionalType::zero())
+    }
+
+    fn sample_x(times: &[FractionalType], values: &[Vec3], interpolation: Interpolation, time: f64) -> f64
+    {
+        sample(times, values, interpolation, num(time)).x.to_num()
+    }
+
+    fn assert_close(actual: f64, expected: f64)
+    {
+        assert!((actual - expected).abs() < 1.0 / 256.0, "{} isn't {}", actual, expected);
+    }
+
+    #[test]
+    fn step_holds_every_keyframe()
+    {
+        let times = times(&[1.0, 2.0, 4.0]);
+        let values = [x(10.0), x(20.0), x(40.0)];
+        for (time, expected) in [(0.0, 10.0), (1.0, 10.0), (1.99, 10.0), (2.0, 20.0), (3.5, 20.0), (4.0, 40.0), (9.0, 40.0)]
+        {
+            assert_eq!(sample_x(&times, &values, Interpolation::Step, time), expected, "at {}", time);
+        }
+    }
+
+    #[test]
+    fn linear_interpolates_between_keyframes()
+    {
+        let times = times(&[1.0, 2.0, 4.0]);
+        let values = [x(10.0), x(20.0), x(40.0)];
+        // before the first and after the last keyframe they're held
+        assert_eq!(sample_x(&times, &values, Interpolation::Linear, 0.0), 10.0);
+        assert_eq!(sample_x(&times, &values, Interpolation::Linear, 5.0), 40.0);
+        // exactly on a keyframe, including the last
+        assert_eq!(sample_x(&times, &values, Interpolation::Linear, 2.0), 20.0);
+        assert_eq!(sample_x(&times, &values, Interpolation::Linear, 4.0), 40.0);
+        assert_close(sample_x(&times, &values, Interpolation::Linear, 1.25), 12.5);
+        assert_close(sample_x(&times, &values, Interpolation::Linear, 3.0), 30.0);
+    }
+
+    #[test]
+    fn linear_rotations_slerp()
+    {
+        let times = times(&[0.0, 1.0]);
+        let half_turn = Quat::new(FractionalType::zero(), FractionalType::zero(), FractionalType::one(), FractionalType::zero());
+        let halfway = sample(&times, &[Quat::identity(), half_turn], Interpolation::Linear, num(0.5));
+        // a quarter turn around y, still unit length
+        let half_sqrt2 = std::f64::consts::FRAC_1_SQRT_2;
+        assert_close(halfway.w.to_num(), half_sqrt2);
+        assert_close(halfway.y.to_num(), half_sqrt2);
+        assert_close(halfway.length().to_num(), 1.0);
+    }
+
+    #[test]
+    fn cubic_spline_passes_through_keyframes()
+    {
+        let times = times(&[1.0, 2.0, 4.0]);
+        // in-tangent, value, out-tangent per keyframe
+        let values = [x(5.0), x(10.0), x(-3.0), x(7.0), x(20.0), x(1.0), x(2.0), x(40.0), x(9.0)];
+        for (time, expected) in [(0.0, 10.0), (1.0, 10.0), (2.0, 20.0), (4.0, 40.0), (6.0, 40.0)]
+        {
+            assert_eq!(sample_x(&times, &values, Interpolation::CubicSpline, time), expected, "at {}", time);
+        }
+    }
+
+    #[test]
+    fn cubic_spline_tangents_scale_by_the_keyframe_span()
+    {
+        // flat values, only the first keyframe's out-tangent of one unit per
+        // second makes the curve move
+        let values = [x(0.0), x(0.0), x(1.0), x(0.0), x(0.0), x(0.0)];
+        // halfway the out-tangent's weight is 1/8 of the span
+        assert_close(sample_x(&times(&[0.0, 1.0]), &values, Interpolation::CubicSpline, 0.5), 0.125);
+        assert_close(sample_x(&times(&[0.0, 2.0]), &values, Interpolation::CubicSpline, 1.0), 0.25);
+        assert_close(sample_x(&times(&[3.0, 7.0]), &values, Interpolation::CubicSpline, 5.0), 0.5);
+        // so right after the keyframe it moves at one unit per second,
+        // whatever the span
+        for end in [1.0, 2.0, 4.0]
+        {
+            assert_close(sample_x(&times(&[0.0, end]), &values, Interpolation::CubicSpline, 1.0 / 64.0), 1.0 / 64.0);
+        }
+    }
+
+    #[test]
+    fn hermite_weights_blend_the_end_values()
+    {
+        for t in [0.0, 0.25, 0.5, 0.75, 1.0]
+        {
+            let [a, _, c, _] = hermite_weights(num(t), num(3.0));
+            assert_close((a + c).to_num(), 1.0);
+        }
+        let at = |t| hermite_weights(num(t), num(3.0)).map(|weight| weight.to_num::<f64>());
+        assert_eq!(at(0.0), [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(at(1.0), [0.0, 0.0, 1.0, 0.0]);
+    }
+}
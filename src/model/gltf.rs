@@ -0,0 +1,316 @@
+use super::animation::{Animation, Channel, Interpolation, Values};
+use super::skin::Skin;
+use super::{Model, ModelError, Node, Transform};
+use crate::fractional_type::FractionalType;
+use crate::math::{Mat3x4, Quat, Vec2, Vec3, Vec4};
+use crate::renderer::texture::Texture;
+use crate::renderer::types::{JointWeights, Mesh, Vertex};
+use ::gltf::animation::util::ReadOutputs;
+use ::gltf::image::Format;
+use ::gltf::mesh::Mode;
+use sdl2::pixels::Color;
+
+// glTF 2.0 loading. Buffers and images are read by the gltf crate, the
+// accessors of every primitive are converted into one Mesh each, in fixed
+// point. Every image becomes a texture, sampled the PSX way whatever the
+// file's samplers ask for. Nodes keep their glTF indices so skins and
+// animations can refer to them. Morph target animation isn't supported.
+
+/// Loads every primitive of every mesh in the file, in the mesh's own space,
+/// the node hierarchy of the default scene (or the first one) placing them,
+/// the skins deforming them and the animations moving the nodes. A file
+/// without scenes shows all of its meshes untransformed.
+pub fn load(path: &std::path::Path) -> Result<Model, ModelError>
+{
+    let (document, buffers, images) = ::gltf::import(path)?;
+    let textures = images.iter()
+        .map(to_texture)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut meshes = Vec::new();
+    // the Model meshes made from each glTF mesh's primitives
+    let mut primitive_meshes = Vec::new();
+    for mesh in document.meshes()
+    {
+        let first = meshes.len();
+        for primitive in mesh.primitives()
+        {
+            if let Some(mesh) = load_primitive(&primitive, &buffers)?
+            {
+                meshes.push(mesh);
+            }
+        }
+        primitive_meshes.push((first..meshes.len()).collect::<Vec<_>>());
+    }
+
+    let Some(scene) = document.default_scene().or_else(|| document.scenes().next())
+    else
+    {
+        return Ok(Model::from_meshes(meshes, textures));
+    };
+    let nodes = document.nodes()
+        .map(|node| {
+            let (translation, rotation, scale) = node.transform().decomposed();
+            let transform = Transform {
+                translation: Vec3::from_f32(&translation),
+                rotation: Quat::from_f32_xyzw(&rotation).normalize(),
+                scale: Vec3::from_f32(&scale),
+            };
+            Node {
+                name: node.name().map(str::to_string),
+                transform,
+                rest_transform: transform,
+                children: node.children().map(|child| child.index()).collect(),
+                meshes: node.mesh().map_or(Vec::new(), |mesh| primitive_meshes[mesh.index()].clone()),
+                skin: node.skin().map(|skin| skin.index()),
+            }
+        })
+        .collect();
+    let roots = scene.nodes().map(|node| node.index()).collect();
+    let skins = document.skins()
+        .map(|skin| load_skin(&skin, &buffers))
+        .collect();
+    let animations = document.animations()
+        .map(|animation| load_animation(&animation, &buffers))
+        .collect::<Result<Vec<_>, _>>()?;
+    let model = Model { meshes, textures, nodes, roots, skins, animations };
+    check_hierarchy(&model)?;
+    check_joints(&model)?;
+    Ok(model)
+}
+
+fn load_skin(skin: &::gltf::Skin, buffers: &[::gltf::buffer::Data]) -> Skin
+{
+    let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+    let reader = skin.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    // without inverse bind matrices every joint is bound at the identity
+    let inverse_bind_matrices = match reader.read_inverse_bind_matrices()
+    {
+        Some(matrices) => matrices.map(|matrix| to_mat3x4(&matrix)).collect(),
+        None => vec![Mat3x4::identity(); joints.len()],
+    };
+    Skin { name: skin.name().map(str::to_string), joints, inverse_bind_matrices }
+}
+
+fn load_animation(animation: &::gltf::Animation, buffers: &[::gltf::buffer::Data]) -> Result<Animation, ModelError>
+{
+    let mut channels = Vec::new();
+    for channel in animation.channels()
+    {
+        let reader = channel.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+        let (Some(inputs), Some(outputs)) = (reader.read_inputs(), reader.read_outputs()) else { continue };
+        let times: Vec<FractionalType> = inputs.map(FractionalType::from_num).collect();
+        let values = match outputs
+        {
+            ReadOutputs::Translations(translations) => Values::Translations(translations.map(|value| Vec3::from_f32(&value)).collect()),
+            ReadOutputs::Rotations(rotations) => Values::Rotations(rotations.into_f32().map(|value| Quat::from_f32_xyzw(&value).normalize()).collect()),
+            ReadOutputs::Scales(scales) => Values::Scales(scales.map(|value| Vec3::from_f32(&value)).collect()),
+            ReadOutputs::MorphTargetWeights(_) => continue,
+        };
+        let interpolation = match channel.sampler().interpolation()
+        {
+            ::gltf::animation::Interpolation::Step => Interpolation::Step,
+            ::gltf::animation::Interpolation::Linear => Interpolation::Linear,
+            ::gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+        };
+        let count = match &values
+        {
+            Values::Translations(values) | Values::Scales(values) => values.len(),
+            Values::Rotations(values) => values.len(),
+        };
+        let expected = if interpolation == Interpolation::CubicSpline { 3 * times.len() } else { times.len() };
+        if times.is_empty() || count != expected
+        {
+            return Err(ModelError::Unsupported("glTF animation channel with mismatched keyframes".to_string()));
+        }
+        channels.push(Channel { node: channel.target().node().index(), interpolation, times, values });
+    }
+    let duration = channels.iter()
+        .filter_map(|channel| channel.times.last().copied())
+        .max()
+        .unwrap_or_default();
+    Ok(Animation { name: animation.name().map(str::to_string), channels, duration })
+}
+
+// glTF matrices are column major, the bottom row of an affine transform is
+// dropped
+fn to_mat3x4(matrix: &[[f32; 4]; 4]) -> Mat3x4
+{
+    let row = |row: usize| Vec4::new(
+        FractionalType::from_num(matrix[0][row]),
+        FractionalType::from_num(matrix[1][row]),
+        FractionalType::from_num(matrix[2][row]),
+        FractionalType::from_num(matrix[3][row])
+    );
+    Mat3x4 { rows: [row(0), row(1), row(2)] }
+}
+
+// every skinned mesh has to stay within the joints of its skin
+fn check_joints(model: &Model) -> Result<(), ModelError>
+{
+    for node in &model.nodes
+    {
+        let Some(skin) = node.skin.map(|skin| &model.skins[skin]) else { continue };
+        if skin.inverse_bind_matrices.len() < skin.joints.len()
+        {
+            return Err(ModelError::Unsupported("glTF skin with fewer inverse bind matrices than joints".to_string()));
+        }
+        let out_of_range = node.meshes.iter()
+            .flat_map(|&mesh| &model.meshes[mesh].joint_weights)
+            .any(|weights| weights.joints.iter().any(|&joint| joint as usize >= skin.joints.len()));
+        if out_of_range
+        {
+            return Err(ModelError::Unsupported("glTF joint indices past the end of the skin".to_string()));
+        }
+    }
+    Ok(())
+}
+
+// the nodes of a scene have to form trees, a node reached twice would be
+// drawn twice or, in a cycle, forever
+fn check_hierarchy(model: &Model) -> Result<(), ModelError>
+{
+    let mut visited = vec![false; model.nodes.len()];
+    let mut pending = model.roots.clone();
+    while let Some(index) = pending.pop()
+    {
+        if std::mem::replace(&mut visited[index], true)
+        {
+            return Err(ModelError::Unsupported("glTF node hierarchy that isn't a tree".to_string()));
+        }
+        pending.extend(&model.nodes[index].children);
+    }
+    Ok(())
+}
+
+// None for primitives without triangles, i.e. points and lines
+fn load_primitive(primitive: &::gltf::Primitive, buffers: &[::gltf::buffer::Data]) -> Result<Option<Mesh>, ModelError>
+{
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let positions: Vec<[f32; 3]> = reader.read_positions()
+        .ok_or_else(|| ModelError::Unsupported("glTF primitive without POSITION".to_string()))?
+        .collect();
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(Iterator::collect);
+    let uvs: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|uvs| uvs.into_f32().collect());
+    let colors: Option<Vec<[u8; 3]>> = reader.read_colors(0).map(|colors| colors.into_rgb_u8().collect());
+    let joints: Option<Vec<[u16; 4]>> = reader.read_joints(0).map(|joints| joints.into_u16().collect());
+    let weights: Option<Vec<[f32; 4]>> = reader.read_weights(0).map(|weights| weights.into_f32().collect());
+    let attribute_counts = [
+        ("NORMAL", normals.as_ref().map(Vec::len)),
+        ("TEXCOORD_0", uvs.as_ref().map(Vec::len)),
+        ("COLOR_0", colors.as_ref().map(Vec::len)),
+        ("JOINTS_0", joints.as_ref().map(Vec::len)),
+        ("WEIGHTS_0", weights.as_ref().map(Vec::len)),
+    ];
+    for (name, count) in attribute_counts
+    {
+        if count.is_some_and(|count| count != positions.len())
+        {
+            return Err(ModelError::Unsupported(format!("glTF primitive with a {} count different from POSITION's", name)));
+        }
+    }
+
+    // the material's base color tints the vertex colors, white without any
+    let pbr = primitive.material().pbr_metallic_roughness();
+    let [red, green, blue, _] = pbr.base_color_factor();
+    let tint = |color: [u8; 3]| Color::RGB(
+        (color[0] as f32 * red).round() as u8,
+        (color[1] as f32 * green).round() as u8,
+        (color[2] as f32 * blue).round() as u8
+    );
+    let vertices: Vec<Vertex> = positions.iter()
+        .enumerate()
+        .map(|(index, position)| Vertex {
+            position: Vec3::from_f32(position),
+            normal: normals.as_ref()
+                .and_then(|normals| normals.get(index))
+                .map_or(Vec3::zero(), |normal| Vec3::from_f32(normal).normalize()),
+            uv: uvs.as_ref()
+                .and_then(|uvs| uvs.get(index))
+                .map_or(Vec2::default(), |uv| Vec2::new(FractionalType::from_num(uv[0]), FractionalType::from_num(uv[1]))),
+            color: tint(colors.as_ref().and_then(|colors| colors.get(index)).copied().unwrap_or([255; 3])),
+        })
+        .collect();
+
+    let indices: Vec<usize> = match reader.read_indices()
+    {
+        Some(indices) => indices.into_u32().map(|index| index as usize).collect(),
+        None => (0..vertices.len()).collect(),
+    };
+    if indices.iter().any(|&index| index >= vertices.len())
+    {
+        return Err(ModelError::Unsupported("glTF indices past the end of the vertices".to_string()));
+    }
+    let triangles = match primitive.mode()
+    {
+        Mode::Triangles => indices.chunks_exact(3).map(|triangle| [triangle[0], triangle[1], triangle[2]]).collect(),
+        // every other triangle of a strip is flipped to keep the winding
+        Mode::TriangleStrip => indices.windows(3)
+            .enumerate()
+            .map(|(index, triangle)| if index % 2 == 0 { [triangle[0], triangle[1], triangle[2]] } else { [triangle[1], triangle[0], triangle[2]] })
+            .collect(),
+        Mode::TriangleFan => (2..indices.len()).map(|index| [indices[0], indices[index - 1], indices[index]]).collect(),
+        Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => return Ok(None),
+    };
+
+    let joint_weights = match (joints, weights)
+    {
+        (Some(joints), Some(weights)) =>
+        {
+            joints.iter().zip(&weights).map(|(&joints, weights)| to_joint_weights(joints, weights)).collect()
+        }
+        _ => Vec::new(),
+    };
+    let mut mesh = Mesh {
+        vertices,
+        triangles,
+        texture: pbr.base_color_texture().map(|info| info.texture().source().index()),
+        joint_weights,
+    };
+    if normals.is_none()
+    {
+        mesh.compute_normals();
+    }
+    Ok(Some(mesh))
+}
+
+// exporters don't always get the weights to add up to one, and fixed point
+// rounding wouldn't keep them there anyway, so they are normalized with the
+// remainder going to the heaviest joint
+fn to_joint_weights(joints: [u16; 4], weights: &[f32; 4]) -> JointWeights
+{
+    let total: f32 = weights.iter().map(|weight| weight.max(0.0)).sum();
+    if total <= 0.0
+    {
+        // bound to the first joint alone
+        return JointWeights { joints, weights: [FractionalType::from_num(1), FractionalType::default(), FractionalType::default(), FractionalType::default()] };
+    }
+    let mut joint_weights = JointWeights {
+        joints,
+        weights: weights.map(|weight| FractionalType::from_num(weight.max(0.0) / total)),
+    };
+    let heaviest = (0..4).max_by_key(|&index| joint_weights.weights[index]).unwrap_or(0);
+    let rest: FractionalType = (0..4).filter(|&index| index != heaviest).map(|index| joint_weights.weights[index]).sum();
+    joint_weights.weights[heaviest] = FractionalType::from_num(1) - rest;
+    joint_weights
+}
+
+fn to_texture(image: &::gltf::image::Data) -> Result<Texture, ModelError>
+{
+    let channels = match image.format
+    {
+        Format::R8 => 1,
+        Format::R8G8 => 2,
+        Format::R8G8B8 => 3,
+        Format::R8G8B8A8 => 4,
+        format => return Err(ModelError::Unsupported(format!("glTF image format {:?}", format))),
+    };
+    // gray images are the first channel, alpha is ignored
+    let texels = image.pixels.chunks_exact(channels)
+        .map(|texel| match channels
+        {
+            1 | 2 => Color::RGB(texel[0], texel[0], texel[0]),
+            _ => Color::RGB(texel[0], texel[1], texel[2]),
+        })
+        .collect();
+    Ok(Texture::new(image.width, image.height, texels))
+}
@@ -0,0 +1,78 @@
+use crate::math::Mat3x4;
+use crate::renderer::preferences::Skinning;
+use crate::renderer::types::JointWeights;
+use num_traits::identities::Zero;
+
+// Skeletal skinning on the CPU, in fixed point like everything else. A skin's
+// joints are ordinary nodes, so posing them is just moving those nodes. Each
+// frame every joint gets a matrix taking a vertex from the bind pose to where
+// the joint has moved it, and every vertex blends the matrices of its joints
+// by their weights (linear blend skinning).
+
+/// A skeleton deforming skinned meshes.
+#[derive(Clone, Default, Debug)]
+pub struct Skin
+{
+    pub name: Option<String>,
+    /// the joint nodes, indices into Model::nodes
+    pub joints: Vec<usize>,
+    /// per joint, from the mesh's bind pose into the joint's space
+    pub inverse_bind_matrices: Vec<Mat3x4>,
+}
+
+impl Skin
+{
+    /// Every joint's world transform after its inverse bind matrix, given the
+    /// local transform and the parent of every node.
+    pub fn joint_matrices(&self, local_transforms: &[Mat3x4], parents: &[Option<usize>]) -> Vec<Mat3x4>
+    {
+        // multiplied from the inverse bind matrix up to the root instead of
+        // reusing the world transforms: skeletons are often scaled down at
+        // the root (Mixamo's by 0.01) and their inverse bind matrices scale
+        // back up, so going down from the root would leave the whole chain
+        // with a few significant bits before scaling them back up
+        self.joints.iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(&joint, inverse_bind_matrix)| {
+                let mut matrix = *inverse_bind_matrix;
+                let mut node = Some(joint);
+                while let Some(index) = node
+                {
+                    matrix = local_transforms[index].compose(&matrix);
+                    node = parents[index];
+                }
+                matrix
+            })
+            .collect()
+    }
+}
+
+/// The transform of one vertex, from the mesh's bind pose to world space.
+/// Skinning::Off keeps the bind pose, i.e. returns the identity.
+pub fn vertex_transform(weights: &JointWeights, joint_matrices: &[Mat3x4], skinning: Skinning) -> Mat3x4
+{
+    match skinning
+    {
+        Skinning::Smooth =>
+        {
+            let mut blended = Mat3x4::default();
+            for (&joint, &weight) in weights.joints.iter().zip(&weights.weights)
+            {
+                if weight.is_zero()
+                {
+                    continue;
+                }
+                for (row, joint_row) in blended.rows.iter_mut().zip(joint_matrices[joint as usize].rows)
+                {
+                    row.x += joint_row.x * weight;
+                    row.y += joint_row.y * weight;
+                    row.z += joint_row.z * weight;
+                    row.w += joint_row.w * weight;
+                }
+            }
+            blended
+        }
+        Skinning::Rigid => joint_matrices[weights.heaviest() as usize],
+        Skinning::Off => Mat3x4::identity(),
+    }
+}
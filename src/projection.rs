@@ -0,0 +1,313 @@
+use crate::fractional_type::{self, FractionalType};
+use crate::math::{Mat3, Mat3x4, Vec2, Vec3};
+use num_traits::identities::{One, Zero};
+
+// Perspective projection described the way the GTE does it: a vertex at
+// camera space (X, Y, Z) lands on screen at
+//   SX = OFX + X * H / Z
+//   SY = OFY - Y * H / Z
+// (the GTE has y pointing down, our world has it pointing up, hence the
+// minus). H is the distance from the eye to the projection plane in pixels,
+// so together with the resolution it determines the field of view.
+//
+// An orthographic projection drops the divide and scales by a fixed number of
+// pixels per world unit instead:
+//   SX = OFX + X * scale
+//   SY = OFY - Y * scale
+// Either way camera space Z is kept as the depth, so clipping against the
+// near and far planes and depth sorting work the same in both modes.
+//
+// The framebuffer is always shown at 4:3 like on a TV, so at most PSX
+// resolutions pixels aren't square (256x240 pixels are wider than tall,
+// 640x240 ones half as wide). X is divided by the pixel aspect ratio to make
+// up for it, which games did by scaling the rotation matrix's first row.
+
+/// The shape of the display the framebuffer is stretched to.
+pub const DISPLAY_ASPECT: (u32, u32) = (4, 3);
+
+// default clipping planes, in world units
+pub const DEFAULT_NEAR: f32 = 0.125;
+pub const DEFAULT_FAR: f32 = 1000.0;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ProjectionMode
+{
+    Perspective,
+    Orthographic,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Projection
+{
+    pub mode: ProjectionMode,
+    /// projection plane distance, in pixels
+    pub h: FractionalType,
+    /// orthographic scale, in pixels per world unit
+    pub scale: FractionalType,
+    /// screen offset OFX/OFY, in pixels
+    pub offset: Vec2,
+    pub resolution: (u32, u32),
+    /// width of a pixel on the display divided by its height
+    pub pixel_aspect: FractionalType,
+    /// clipping planes, as camera space depths
+    pub near: FractionalType,
+    pub far: FractionalType,
+}
+
+impl Projection
+{
+    pub fn new(h: FractionalType, offset: Vec2, resolution: (u32, u32)) -> Self
+    {
+        Self {
+            mode: ProjectionMode::Perspective,
+            h,
+            // objects one world unit away look the same in both modes
+            scale: h,
+            offset,
+            resolution,
+            pixel_aspect: pixel_aspect(resolution),
+            near: FractionalType::from_num(DEFAULT_NEAR),
+            far: FractionalType::from_num(DEFAULT_FAR),
+        }
+    }
+
+    /// A projection with the screen offset in the middle of the screen.
+    pub fn centered(h: FractionalType, resolution: (u32, u32)) -> Self
+    {
+        Self::new(h, screen_center(resolution), resolution)
+    }
+
+    /// A centered projection with the given horizontal field of view, in
+    /// fractional_type::ANGLE_ONE_TURN units.
+    pub fn from_fov(fov: i32, resolution: (u32, u32)) -> Self
+    {
+        Self::centered(fov_to_h(fov, square_width(resolution)), resolution)
+    }
+
+    /// Horizontal field of view, in fractional_type::ANGLE_ONE_TURN units.
+    pub fn fov(&self) -> i32
+    {
+        h_to_fov(self.h, square_width(self.resolution))
+    }
+
+    pub fn set_fov(&mut self, fov: i32)
+    {
+        self.h = fov_to_h(fov, square_width(self.resolution));
+    }
+
+    /// Changes the resolution, keeping the field of view and the size things
+    /// appear on the display, and recentering the screen offset.
+    pub fn set_resolution(&mut self, resolution: (u32, u32))
+    {
+        let fov = self.fov();
+        self.scale = self.scale * FractionalType::from_num(resolution.1) / FractionalType::from_num(self.resolution.1);
+        self.resolution = resolution;
+        self.pixel_aspect = pixel_aspect(resolution);
+        self.offset = screen_center(resolution);
+        self.set_fov(fov);
+    }
+
+    /// The width of the screen in pixels as tall as the framebuffer's, i.e.
+    /// how wide it would be with square pixels.
+    pub fn square_width(&self) -> u32
+    {
+        square_width(self.resolution)
+    }
+
+    /// Sets the orthographic scale so that objects `distance` world units in
+    /// front of the camera keep their perspective size.
+    pub fn match_scale_at(&mut self, distance: FractionalType)
+    {
+        if distance > FractionalType::zero()
+        {
+            self.scale = self.h / distance;
+        }
+    }
+
+    /// Whether a point at camera space depth `depth` is cut by the near or far
+    /// plane.
+    pub fn clips(&self, depth: FractionalType) -> bool
+    {
+        depth < self.near || depth > self.far
+    }
+
+    // Maps homogeneous camera space (X, Y, Z, 1) to homogeneous image
+    // coordinates. For perspective
+    //   K = (H/a  0 OFX 0)
+    //       (0   -H OFY 0)
+    //       (0    0  1  0)
+    // and for orthographic
+    //   K = (s/a  0  0 OFX)
+    //       (0   -s  0 OFY)
+    //       (0    0  0  1 )
+    // where a is the pixel aspect ratio.
+    pub fn intrinsic_matrix(&self) -> Mat3x4
+    {
+        let (zero, one) = (FractionalType::zero(), FractionalType::one());
+        match self.mode
+        {
+            ProjectionMode::Perspective => Mat3x4::new(
+                Mat3::from_rows([
+                    Vec3::new(self.h / self.pixel_aspect, zero, self.offset.x),
+                    Vec3::new(zero, -self.h, self.offset.y),
+                    Vec3::new(zero, zero, one),
+                ]),
+                Vec3::zero()
+            ),
+            ProjectionMode::Orthographic => Mat3x4::new(
+                Mat3::diagonal(Vec3::new(self.scale / self.pixel_aspect, -self.scale, zero)),
+                Vec3::new(self.offset.x, self.offset.y, one)
+            ),
+        }
+    }
+}
+
+/// The pixel aspect ratio of `resolution` shown at DISPLAY_ASPECT, e.g. 1.25
+/// for 256x240.
+pub fn pixel_aspect(resolution: (u32, u32)) -> FractionalType
+{
+    let (display_x, display_y) = DISPLAY_ASPECT;
+    FractionalType::from_num(display_x * resolution.1) / FractionalType::from_num(display_y * resolution.0)
+}
+
+fn square_width(resolution: (u32, u32)) -> u32
+{
+    resolution.1 * DISPLAY_ASPECT.0 / DISPLAY_ASPECT.1
+}
+
+fn screen_center(resolution: (u32, u32)) -> Vec2
+{
+    Vec2::new(
+        FractionalType::from_num(resolution.0 / 2),
+        FractionalType::from_num(resolution.1 / 2)
+    )
+}
+
+/// The H that gives a horizontal field of view of `fov` (in
+/// fractional_type::ANGLE_ONE_TURN units) on a screen `width` square pixels
+/// wide.
+/// The field of view is kept strictly between zero and half a turn.
+pub fn fov_to_h(fov: i32, width: u32) -> FractionalType
+{
+    let half_fov = fov.clamp(2, fractional_type::ANGLE_ONE_TURN / 2 - 2) / 2;
+    FractionalType::from_num(width / 2) * fractional_type::cos(half_fov) / fractional_type::sin(half_fov)
+}
+
+/// The horizontal field of view, in fractional_type::ANGLE_ONE_TURN units, of
+/// a projection plane distance `h` on a screen `width` square pixels wide.
+pub fn h_to_fov(h: FractionalType, width: u32) -> i32
+{
+    2 * fractional_type::atan2(FractionalType::from_num(width / 2), h)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn fov_and_h_round_trip()
+    {
+        for width in [320, 640]
+        {
+            for fov in (64..2000).step_by(2)
+            {
+                let round_trip = h_to_fov(fov_to_h(fov, width), width);
+                assert!((round_trip - fov).abs() <= 2, "fov {} at {} came back as {}", fov, width, round_trip);
+            }
+        }
+    }
+
+    #[test]
+    fn fov_to_h_known_values()
+    {
+        // 90 degrees puts the screen edge at 45 degrees, H is half the width
+        assert_eq!(fov_to_h(fractional_type::ANGLE_QUARTER_TURN, 320), FractionalType::from_num(160));
+        assert_eq!(h_to_fov(FractionalType::from_num(160), 320), fractional_type::ANGLE_QUARTER_TURN);
+        // the field of view is horizontal on a 4:3 screen with square pixels,
+        // whatever the framebuffer width
+        let projection = Projection::from_fov(fractional_type::ANGLE_QUARTER_TURN, (640, 240));
+        assert_eq!(projection.h, FractionalType::from_num(160));
+        assert_eq!(projection.fov(), fractional_type::ANGLE_QUARTER_TURN);
+    }
+
+    #[test]
+    fn pixel_aspect_at_4_3()
+    {
+        let aspect = |resolution| pixel_aspect(resolution).to_num::<f64>();
+        assert_eq!(aspect((320, 240)), 1.0);
+        assert_eq!(aspect((640, 480)), 1.0);
+        assert_eq!(aspect((256, 240)), 1.25);
+        assert_eq!(aspect((640, 240)), 0.5);
+        assert!((aspect((368, 240)) - 320.0 / 368.0).abs() < 1.0 / 1024.0);
+        // a square in camera space stays square on the display: as many
+        // pixels wide as tall at 320x240, twice as many at 640x240
+        for (resolution, width) in [((320, 240), 80), ((640, 240), 160), ((256, 240), 64)]
+        {
+            let projection = Projection::from_fov(fractional_type::ANGLE_QUARTER_TURN, resolution);
+            let corner = projection.intrinsic_matrix().transform_point(Vec3::new(
+                FractionalType::from_num(1),
+                FractionalType::from_num(1),
+                FractionalType::from_num(2)
+            ));
+            let x: f64 = (corner.x / corner.z - projection.offset.x).to_num();
+            let y: f64 = (projection.offset.y - corner.y / corner.z).to_num();
+            assert_eq!((x, y), (width as f64, 80.0), "at {:?}", resolution);
+        }
+    }
+
+    fn project(projection: &Projection, point: Vec3) -> (f64, f64)
+    {
+        let image = projection.intrinsic_matrix().transform_point(point);
+        let (x, y) = match projection.mode
+        {
+            ProjectionMode::Perspective => (image.x / image.z, image.y / image.z),
+            ProjectionMode::Orthographic => (image.x, image.y),
+        };
+        (x.to_num(), y.to_num())
+    }
+
+    fn point(x: f64, y: f64, z: f64) -> Vec3
+    {
+        Vec3::new(FractionalType::from_num(x), FractionalType::from_num(y), FractionalType::from_num(z))
+    }
+
+    #[test]
+    fn orthographic_ignores_depth()
+    {
+        let mut projection = Projection::from_fov(fractional_type::ANGLE_QUARTER_TURN, (320, 240));
+        projection.mode = ProjectionMode::Orthographic;
+        projection.scale = FractionalType::from_num(40);
+        assert_eq!(project(&projection, point(1.0, 0.5, 2.0)), (200.0, 100.0));
+        assert_eq!(project(&projection, point(1.0, 0.5, 50.0)), (200.0, 100.0));
+        // pixels half as wide at 640x240 take twice as many of them
+        projection.set_resolution((640, 240));
+        assert_eq!(project(&projection, point(1.0, 0.5, 2.0)), (400.0, 100.0));
+    }
+
+    #[test]
+    fn orthographic_scale_matches_perspective_at_a_distance()
+    {
+        let mut projection = Projection::from_fov(fractional_type::ANGLE_QUARTER_TURN, (256, 240));
+        let near_edge = point(1.5, -0.75, 6.0);
+        let perspective = project(&projection, near_edge);
+        projection.match_scale_at(FractionalType::from_num(6));
+        projection.mode = ProjectionMode::Orthographic;
+        let orthographic = project(&projection, near_edge);
+        assert!((perspective.0 - orthographic.0).abs() < 0.01 && (perspective.1 - orthographic.1).abs() < 0.01);
+        // zero and negative distances keep the scale
+        let scale = projection.scale;
+        projection.match_scale_at(FractionalType::from_num(0));
+        assert_eq!(projection.scale, scale);
+    }
+
+    #[test]
+    fn orthographic_scale_follows_the_line_count()
+    {
+        let mut projection = Projection::from_fov(fractional_type::ANGLE_QUARTER_TURN, (320, 240));
+        projection.scale = FractionalType::from_num(40);
+        projection.set_resolution((640, 480));
+        // the same size on the display
+        assert_eq!(projection.scale, FractionalType::from_num(80));
+    }
+}
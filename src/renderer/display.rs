@@ -0,0 +1,64 @@
+use crate::projection::DISPLAY_ASPECT;
+use sdl2::rect::Rect;
+
+// Getting the framebuffer onto the window. Like a TV showing a PSX the image
+// is always 4:3, whatever the framebuffer's resolution, and it's scaled with
+// nearest neighbour so every framebuffer pixel stays a sharp block.
+
+/// The horizontal resolutions of the PSX GPU.
+pub const HORIZONTAL_RESOLUTIONS: [u32; 5] = [256, 320, 368, 512, 640];
+/// Progressive and interlaced NTSC.
+pub const VERTICAL_RESOLUTIONS: [u32; 2] = [240, 480];
+
+/// The next PSX horizontal resolution after `width`, wrapping around.
+pub fn next_width(width: u32) -> u32
+{
+    HORIZONTAL_RESOLUTIONS.iter()
+        .copied()
+        .find(|&next| next > width)
+        .unwrap_or(HORIZONTAL_RESOLUTIONS[0])
+}
+
+/// The other PSX vertical resolution.
+pub fn next_height(height: u32) -> u32
+{
+    VERTICAL_RESOLUTIONS.iter()
+        .copied()
+        .find(|&next| next > height)
+        .unwrap_or(VERTICAL_RESOLUTIONS[0])
+}
+
+/// A window size showing `resolution` at 4:3 with whole lines, at least 480
+/// lines tall.
+pub fn default_window_size(resolution: (u32, u32)) -> (u32, u32)
+{
+    let lines = resolution.1.max(1);
+    let height = lines * (480 / lines).max(1);
+    (height * DISPLAY_ASPECT.0 / DISPLAY_ASPECT.1, height)
+}
+
+/// Where in a window of `window` size to show a framebuffer of `resolution`:
+/// the largest 4:3 rectangle that fits, centered. With `integer_scaling` its
+/// height is a whole multiple of the framebuffer's so every line is equally
+/// tall; pixels can't be equally wide anyway at most resolutions.
+pub fn viewport(window: (u32, u32), resolution: (u32, u32), integer_scaling: bool) -> Rect
+{
+    let (display_x, display_y) = DISPLAY_ASPECT;
+    let lines = resolution.1.max(1);
+    let fit_height = window.1.min(window.0 * display_y / display_x);
+    let height = if integer_scaling && fit_height >= lines
+    {
+        fit_height / lines * lines
+    }
+    else
+    {
+        fit_height
+    };
+    let width = height * display_x / display_y;
+    Rect::new(
+        (window.0.saturating_sub(width) / 2) as i32,
+        (window.1.saturating_sub(height) / 2) as i32,
+        width.max(1),
+        height.max(1)
+    )
+}
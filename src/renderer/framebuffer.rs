@@ -0,0 +1,219 @@
+use sdl2::pixels::Color;
+
+// Everything is drawn into this software framebuffer, which is uploaded to an
+// SDL texture once per frame, instead of drawing point by point through the
+// SDL canvas. It's the stand-in for the PSX's VRAM display area.
+
+/// One half of an interlaced frame: the even or the odd lines.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Field
+{
+    Even,
+    Odd,
+}
+
+impl Field
+{
+    /// The field after this one, fields alternate every frame.
+    pub fn next(self) -> Self
+    {
+        match self
+        {
+            Field::Even => Field::Odd,
+            Field::Odd => Field::Even,
+        }
+    }
+
+    pub fn contains(self, y: i32) -> bool
+    {
+        (y & 1 == 0) == (self == Field::Even)
+    }
+}
+
+/// Counters for one frame of rendering, shown by the debug overlay.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct RenderStats
+{
+    pub primitives_submitted: u32,
+    /// clipped, back facing or degenerate primitives that were dropped
+    pub primitives_culled: u32,
+    pub primitives_drawn: u32,
+    pub pixels_filled: u32,
+}
+
+pub struct Framebuffer
+{
+    width: u32,
+    height: u32,
+    // RGB24, row by row
+    pixels: Vec<u8>,
+    // when set, only the lines of this field are drawn to
+    field: Option<Field>,
+    pub stats: RenderStats,
+}
+
+impl Framebuffer
+{
+    pub const BYTES_PER_PIXEL: usize = 3;
+
+    pub fn new(width: u32, height: u32) -> Self
+    {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * Self::BYTES_PER_PIXEL],
+            field: None,
+            stats: RenderStats::default(),
+        }
+    }
+
+    pub fn width(&self) -> u32
+    {
+        self.width
+    }
+
+    pub fn height(&self) -> u32
+    {
+        self.height
+    }
+
+    /// The pixels in RGB24, ready to upload to a texture or save as an image.
+    pub fn pixels(&self) -> &[u8]
+    {
+        &self.pixels
+    }
+
+    /// Bytes per row.
+    pub fn pitch(&self) -> usize
+    {
+        self.width as usize * Self::BYTES_PER_PIXEL
+    }
+
+    pub fn field(&self) -> Option<Field>
+    {
+        self.field
+    }
+
+    /// Restricts drawing to the lines of one field like the PSX GPU does in
+    /// interlaced mode, or lifts the restriction with None. Lines of the
+    /// other field keep what was drawn to them before.
+    pub fn set_field(&mut self, field: Option<Field>)
+    {
+        self.field = field;
+    }
+
+    /// Fills the framebuffer, or only the current field's lines, and starts a
+    /// new frame's statistics.
+    pub fn clear(&mut self, color: Color)
+    {
+        let field = self.field;
+        for (y, row) in self.pixels.chunks_exact_mut(self.width as usize * Self::BYTES_PER_PIXEL).enumerate()
+        {
+            if field.is_some_and(|field| !field.contains(y as i32))
+            {
+                continue;
+            }
+            for pixel in row.chunks_exact_mut(Self::BYTES_PER_PIXEL)
+            {
+                pixel.copy_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+        self.stats = RenderStats::default();
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize>
+    {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height
+        {
+            return None;
+        }
+        if self.field.is_some_and(|field| !field.contains(y))
+        {
+            return None;
+        }
+        Some((y as usize * self.width as usize + x as usize) * Self::BYTES_PER_PIXEL)
+    }
+
+    /// Sets one pixel, anything outside the framebuffer is ignored.
+    pub fn put_pixel(&mut self, x: i32, y: i32, color: Color)
+    {
+        if let Some(index) = self.index(x, y)
+        {
+            self.pixels[index..index + Self::BYTES_PER_PIXEL].copy_from_slice(&[color.r, color.g, color.b]);
+            self.stats.pixels_filled += 1;
+        }
+    }
+
+    /// Reads a pixel of either field.
+    pub fn get_pixel(&self, x: i32, y: i32) -> Option<Color>
+    {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height
+        {
+            return None;
+        }
+        let index = (y as usize * self.width as usize + x as usize) * Self::BYTES_PER_PIXEL;
+        Some(Color::RGB(self.pixels[index], self.pixels[index + 1], self.pixels[index + 2]))
+    }
+
+    /// The pixels with `field`'s lines doubled over the other field's, i.e.
+    /// bob deinterlaced: no combing, at half the vertical resolution.
+    pub fn bob(&self, field: Field) -> Vec<u8>
+    {
+        let pitch = self.pitch();
+        let mut pixels = self.pixels.clone();
+        for (y, row) in pixels.chunks_exact_mut(pitch).enumerate()
+        {
+            let y = y as i32;
+            if field.contains(y)
+            {
+                continue;
+            }
+            // the neighbouring line of `field`, above for the even field and
+            // below for the odd one
+            let source = if field == Field::Even { y - 1 } else { y + 1 };
+            let source = source.clamp(0, self.height as i32 - 1) as usize;
+            row.copy_from_slice(&self.pixels[source * pitch..(source + 1) * pitch]);
+        }
+        pixels
+    }
+
+    /// Bresenham line including both end points, clipped to the framebuffer.
+    pub fn draw_line(&mut self, from: (i32, i32), to: (i32, i32), color: Color)
+    {
+        let (mut x, mut y) = from;
+        let (dx, dy) = ((to.0 - x).abs(), -(to.1 - y).abs());
+        let (step_x, step_y) = ((to.0 - x).signum(), (to.1 - y).signum());
+        let mut error = dx + dy;
+        loop
+        {
+            self.put_pixel(x, y, color);
+            if (x, y) == to
+            {
+                break;
+            }
+            let doubled_error = 2 * error;
+            if doubled_error >= dy
+            {
+                error += dy;
+                x += step_x;
+            }
+            if doubled_error <= dx
+            {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Fills a rectangle, clipped to the framebuffer.
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Color)
+    {
+        for row in y.max(0)..(y + height as i32).min(self.height as i32)
+        {
+            for column in x.max(0)..(x + width as i32).min(self.width as i32)
+            {
+                self.put_pixel(column, row, color);
+            }
+        }
+    }
+}
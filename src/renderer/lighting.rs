@@ -0,0 +1,47 @@
+use crate::fractional_type::FractionalType;
+use crate::math::Vec3;
+use num_traits::identities::{One, Zero};
+use sdl2::pixels::Color;
+
+// A single directional light plus ambient, evaluated per vertex or per face
+// like the GTE's NCS/NCCS commands. There's no specular, PSX games hardly
+// ever had any.
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DirectionalLight
+{
+    /// unit vector pointing towards the light, in world space
+    pub direction: Vec3,
+    /// brightness of surfaces facing away from the light, 0 to 1
+    pub ambient: FractionalType,
+}
+
+impl Default for DirectionalLight
+{
+    fn default() -> Self
+    {
+        Self {
+            // from the upper left, slightly behind the default camera
+            direction: Vec3::new(FractionalType::from_num(-1), FractionalType::from_num(2), FractionalType::from_num(1)).normalize(),
+            ambient: FractionalType::from_num(0.25),
+        }
+    }
+}
+
+impl DirectionalLight
+{
+    /// How brightly a surface with `normal` is lit, 0 to 1. A zero normal
+    /// only gets ambient light.
+    pub fn intensity(&self, normal: Vec3) -> FractionalType
+    {
+        let diffuse = normal.dot(self.direction).max(FractionalType::zero());
+        (self.ambient + (FractionalType::one() - self.ambient) * diffuse).min(FractionalType::one())
+    }
+}
+
+/// Scales every channel of `color` by `intensity`, 0 to 1.
+pub fn scale_color(color: Color, intensity: FractionalType) -> Color
+{
+    let scale = |channel: u8| (FractionalType::from_num(channel) * intensity).round().to_num::<i32>().clamp(0, 255) as u8;
+    Color::RGB(scale(color.r), scale(color.g), scale(color.b))
+}
@@ -0,0 +1,10 @@
+pub mod display;
+pub mod font;
+pub mod framebuffer;
+pub mod lighting;
+pub mod overlay;
+pub mod pipeline;
+pub mod preferences;
+pub mod rasterizer;
+pub mod texture;
+pub mod types;
@@ -0,0 +1,49 @@
+use super::font::{self, GLYPH_SIZE};
+use super::framebuffer::Framebuffer;
+use sdl2::pixels::Color;
+
+// Debug overlay: lines of text in the top left corner over a dark backdrop,
+// drawn with the embedded bitmap font straight into the framebuffer.
+
+const MARGIN: i32 = 4;
+const TEXT_COLOR: Color = Color::RGB(255, 255, 160);
+const BACKDROP_COLOR: Color = Color::RGB(0, 0, 48);
+
+/// Draws `text` with its top left corner at (x, y). Newlines are not
+/// handled, use one call per line.
+pub fn draw_text(framebuffer: &mut Framebuffer, x: i32, y: i32, text: &str, color: Color)
+{
+    for (index, ch) in text.chars().enumerate()
+    {
+        let glyph = font::glyph(ch);
+        let glyph_x = x + index as i32 * GLYPH_SIZE;
+        for (row, bits) in glyph.iter().enumerate()
+        {
+            for column in 0..GLYPH_SIZE
+            {
+                if bits & (0x80 >> column) != 0
+                {
+                    framebuffer.put_pixel(glyph_x + column, y + row as i32, color);
+                }
+            }
+        }
+    }
+}
+
+/// Draws the overlay. Call it after the scene, and read the frame's
+/// statistics before, since the overlay's own pixels count as filled.
+pub fn draw(framebuffer: &mut Framebuffer, lines: &[String])
+{
+    let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) as i32;
+    framebuffer.fill_rect(
+        0,
+        0,
+        (columns * GLYPH_SIZE + 2 * MARGIN) as u32,
+        (lines.len() as i32 * GLYPH_SIZE + 2 * MARGIN) as u32,
+        BACKDROP_COLOR
+    );
+    for (index, line) in lines.iter().enumerate()
+    {
+        draw_text(framebuffer, MARGIN, MARGIN + index as i32 * GLYPH_SIZE, line, TEXT_COLOR);
+    }
+}
@@ -0,0 +1,226 @@
+use super::framebuffer::Framebuffer;
+use super::lighting::{self, DirectionalLight};
+use super::preferences::{RenderMode, RenderPreferences, SortMode};
+use super::rasterizer::{self, ScreenVertex, Shading};
+use super::texture::Texture;
+use super::types::{self, Mesh};
+use crate::camera::Camera;
+use crate::fractional_type::FractionalType;
+use crate::math::{Mat3x4, Vec3};
+use crate::model::{skin, Model};
+use crate::projection::ProjectionMode;
+use num_traits::identities::{One, Zero};
+use sdl2::pixels::Color;
+
+// Draws a model in world space the way the preferences ask for: every vertex
+// of every mesh instance is skinned and transformed once, triangles are
+// clipped, culled and sorted, then drawn
+// in up to three passes (the render mode's main pass plus the optional
+// wireframe and vertex layers).
+
+const WIREFRAME_COLOR: Color = Color::RGB(191, 191, 191);
+const VERTEX_COLOR: Color = Color::RGB(255, 255, 255);
+// textured polygons are shaded around the GPU's neutral 128 so texels keep
+// their color where fully lit
+const TEXTURE_SHADE: Color = Color::RGB(128, 128, 128);
+
+/// Draws every mesh instance of `model`, adding to the framebuffer's
+/// statistics. Meshes without a texture of their own use `fallback_texture`.
+pub fn draw_model(
+    framebuffer: &mut Framebuffer,
+    camera: &Camera,
+    model: &Model,
+    fallback_texture: &Texture,
+    light: &DirectionalLight,
+    preferences: &RenderPreferences
+)
+{
+    let mode = preferences.render_mode;
+    let instances = model.instances();
+    let joint_matrices = model.joint_matrices();
+    // every vertex is transformed once and shared by the passes below
+    let meshes: Vec<TransformedMesh> = instances.iter()
+        .map(|instance| TransformedMesh::new(
+            &model.meshes[instance.mesh],
+            &instance.transform,
+            instance.skin.map(|skin| &joint_matrices[skin][..]),
+            camera,
+            light,
+            preferences
+        ))
+        .collect();
+
+    // a triangle is dropped as soon as one vertex is clipped, the rest are
+    // culled by their winding and drawn back to front (painter's algorithm),
+    // across all instances so overlapping parts sort correctly
+    let mut triangles: Vec<(usize, &[usize; 3], FractionalType)> = Vec::new();
+    for (instance_index, (instance, transformed)) in instances.iter().zip(&meshes).enumerate()
+    {
+        let mesh = &model.meshes[instance.mesh];
+        let visible = mesh.triangles.iter()
+            .filter(|indices| !indices.iter().any(|&idx| transformed.clipped[idx]))
+            .filter(|indices| {
+                let area = rasterizer::signed_area(&indices.map(|idx| transformed.vertices[idx]));
+                !preferences.culling.culls(if transformed.is_mirrored(indices) { -area } else { area })
+            })
+            .map(|indices| {
+                let depths = indices.map(|idx| transformed.depths[idx]);
+                let depth = match preferences.sort_mode
+                {
+                    SortMode::None => FractionalType::default(),
+                    SortMode::Average => depths.iter().sum(),
+                    SortMode::Farthest => depths.iter().copied().max().unwrap_or_default(),
+                    SortMode::Nearest => depths.iter().copied().min().unwrap_or_default(),
+                };
+                (instance_index, indices, depth)
+            });
+        triangles.extend(visible);
+    }
+    // stable, so SortMode::None keeps the model's order
+    triangles.sort_by_key(|(_, _, depth)| std::cmp::Reverse(*depth));
+    let submitted: usize = instances.iter().map(|instance| model.meshes[instance.mesh].triangles.len()).sum();
+    let stats = &mut framebuffer.stats;
+    stats.primitives_submitted += submitted as u32;
+    stats.primitives_culled += (submitted - triangles.len()) as u32;
+    let mut drawn = triangles.len() as u32;
+
+    if mode.is_solid()
+    {
+        for &(instance_index, indices, _) in &triangles
+        {
+            let transformed = &meshes[instance_index];
+            let mesh = &model.meshes[instances[instance_index].mesh];
+            let shading = match mode
+            {
+                RenderMode::Flat =>
+                {
+                    let color = mesh.vertices[indices[0]].color;
+                    let [a, b, c] = indices.map(|idx| transformed.positions[idx]);
+                    let normal = types::triangle_normal(a, b, c);
+                    let normal = if transformed.is_mirrored(indices) { -normal } else { normal };
+                    Shading::Flat(lighting::scale_color(color, light.intensity(normal)))
+                }
+                RenderMode::Textured => Shading::Textured(model.texture(mesh).unwrap_or(fallback_texture)),
+                _ => Shading::Gouraud,
+            };
+            let vertices = indices.map(|idx| transformed.vertices[idx]);
+            if !rasterizer::draw_triangle(framebuffer, vertices, shading, preferences)
+            {
+                // degenerate on screen
+                framebuffer.stats.primitives_culled += 1;
+                drawn -= 1;
+            }
+        }
+    }
+    framebuffer.stats.primitives_drawn += drawn;
+
+    // the edges of the remaining triangles, i.e. the polygon outlines plus
+    // the fan diagonals, either as the main pass or over the solid one
+    if mode == RenderMode::Wireframe || (mode.is_solid() && preferences.wireframe_overlay)
+    {
+        for &(instance_index, indices, _) in &triangles
+        {
+            let pixels = indices.map(|idx| meshes[instance_index].vertices[idx].pixel());
+            framebuffer.draw_line(pixels[0], pixels[1], WIREFRAME_COLOR);
+            framebuffer.draw_line(pixels[1], pixels[2], WIREFRAME_COLOR);
+            framebuffer.draw_line(pixels[0], pixels[2], WIREFRAME_COLOR);
+        }
+    }
+
+    if mode == RenderMode::Points || preferences.vertex_overlay
+    {
+        for transformed in &meshes
+        {
+            for (vertex, _) in transformed.vertices.iter().zip(&transformed.clipped).filter(|(_, clipped)| !**clipped)
+            {
+                let (x, y) = vertex.pixel();
+                framebuffer.put_pixel(x, y, VERTEX_COLOR);
+            }
+        }
+    }
+}
+
+// a mesh instance's vertices projected to the screen, lit and fogged
+struct TransformedMesh
+{
+    vertices: Vec<ScreenVertex>,
+    // in world space, for flat shading
+    positions: Vec<Vec3>,
+    depths: Vec<FractionalType>,
+    clipped: Vec<bool>,
+    // whether each vertex's transform mirrors, which flips the winding of
+    // the triangles it moves
+    mirrored: Vec<bool>,
+}
+
+impl TransformedMesh
+{
+    // skinned meshes pass their skin's joint matrices, which then replace
+    // `transform` vertex by vertex
+    fn new(
+        mesh: &Mesh,
+        transform: &Mat3x4,
+        joint_matrices: Option<&[Mat3x4]>,
+        camera: &Camera,
+        light: &DirectionalLight,
+        preferences: &RenderPreferences
+    ) -> Self
+    {
+        let transforms: Vec<Mat3x4> = match joint_matrices
+        {
+            Some(joint_matrices) => mesh.joint_weights.iter()
+                .map(|weights| skin::vertex_transform(weights, joint_matrices, preferences.skinning))
+                .collect(),
+            None => vec![*transform; mesh.vertices.len()],
+        };
+        let positions: Vec<_> = mesh.positions()
+            .zip(&transforms)
+            .map(|(&position, transform)| transform.transform_point(position))
+            .collect();
+        // camera space depth means the same in both projection modes, so
+        // clipping and sorting don't have to care which one is active
+        let depths: Vec<FractionalType> = positions.iter()
+            .map(|position| camera.depth(position))
+            .collect();
+        let clipped = depths.iter()
+            .map(|depth| camera.projection().clips(*depth))
+            .collect();
+        // an orthographic projection keeps texture mapping affine either way
+        let orthographic = camera.projection().mode == ProjectionMode::Orthographic;
+        // exact for rotations and uniform scales, which is all the bundled
+        // models use
+        let normals: Vec<Vec3> = mesh.vertices.iter()
+            .zip(&transforms)
+            .map(|(vertex, transform)| transform.transform_vector(vertex.normal).normalize())
+            .collect();
+        let vertices = mesh.vertices.iter()
+            .zip(&normals)
+            .zip(positions.iter().zip(&depths))
+            .map(|((vertex, &normal), (position, &depth))| {
+                let shade = if preferences.render_mode == RenderMode::Textured { TEXTURE_SHADE } else { vertex.color };
+                ScreenVertex {
+                    color: lighting::scale_color(shade, light.intensity(normal)),
+                    uv: vertex.uv,
+                    fog: preferences.fog_amount(depth),
+                    ..ScreenVertex::new(
+                        camera.project(position),
+                        if orthographic { FractionalType::one() } else { depth },
+                        preferences.vertex_snapping
+                    )
+                }
+            })
+            .collect();
+        // per vertex, a skeleton can mirror some joints and not others
+        let mirrored = transforms.iter()
+            .map(|transform| transform.linear().determinant() < FractionalType::zero())
+            .collect();
+        Self { vertices, positions, depths, clipped, mirrored }
+    }
+
+    // a triangle blending across a mirrored joint's boundary goes with the
+    // majority of its vertices
+    fn is_mirrored(&self, indices: &[usize; 3]) -> bool
+    {
+        indices.iter().filter(|&&idx| self.mirrored[idx]).count() >= 2
+    }
+}
@@ -1,4 +1,606 @@
-struct RenderPreferences
+use super::display;
+use crate::fractional_type::FractionalType;
+use num_traits::identities::{One, Zero};
+use sdl2::pixels::Color;
+
+// Every renderer setting in one place. They are passed down the pipeline as a
+// whole, can be changed while the viewer runs, and are stored in a config
+// file with one setting per line, e.g.
+//
+//   # closer to a modern GPU
+//   resolution = 640x480
+//   color_depth = rgb24
+//   texture_mapping = perspective
+//   texture_filtering = bilinear
+//
+// Settings missing from the file keep their defaults. Any of them can also be
+// overridden from the command line with --name=value.
+
+pub const DEFAULT_PATH: &str = "render.cfg";
+
+// enums stored in the config file by name
+macro_rules! setting
 {
-    
-}
\ No newline at end of file
+    ($(#[$meta:meta])* $setting:ident { $($(#[$variant_meta:meta])* $variant:ident => $name:literal,)* }) =>
+    {
+        $(#[$meta])*
+        #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+        pub enum $setting
+        {
+            $($(#[$variant_meta])* $variant,)*
+        }
+
+        impl $setting
+        {
+            pub const ALL: &'static [$setting] = &[$($setting::$variant,)*];
+
+            /// The name used in the config file.
+            pub fn name(self) -> &'static str
+            {
+                match self
+                {
+                    $($setting::$variant => $name,)*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self>
+            {
+                Self::ALL.iter().copied().find(|value| value.name() == name)
+            }
+
+            /// The next value, wrapping around, for cycling through them with
+            /// a key.
+            pub fn next(self) -> Self
+            {
+                let index = Self::ALL.iter().position(|&value| value == self).unwrap_or(0);
+                Self::ALL[(index + 1) % Self::ALL.len()]
+            }
+        }
+
+        impl std::fmt::Display for $setting
+        {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+            {
+                f.write_str(self.name())
+            }
+        }
+    };
+}
+
+setting!
+{
+    /// The main pass: what a triangle is drawn as.
+    RenderMode
+    {
+        Points => "points",
+        Wireframe => "wireframe",
+        Flat => "flat",
+        Gouraud => "gouraud",
+        Textured => "textured",
+    }
+}
+
+setting!
+{
+    /// How texture coordinates are interpolated across a triangle.
+    TextureMapping
+    {
+        /// linearly in screen space like the PSX, textures warp up close
+        Affine => "affine",
+        Perspective => "perspective",
+    }
+}
+
+setting!
+{
+    TextureFiltering
+    {
+        Nearest => "nearest",
+        Bilinear => "bilinear",
+    }
+}
+
+setting!
+{
+    /// Which triangles are dropped by their winding on screen.
+    Culling
+    {
+        None => "none",
+        Back => "back",
+        Front => "front",
+    }
+}
+
+setting!
+{
+    /// The depth triangles are sorted by for the painter's algorithm, like
+    /// the GTE's AVSZ3 or picking the largest Z for the ordering table.
+    SortMode
+    {
+        /// drawn in the order the model lists them
+        None => "none",
+        Average => "average",
+        Farthest => "farthest",
+        Nearest => "nearest",
+    }
+}
+
+setting!
+{
+    /// How an interlaced framebuffer is shown.
+    Deinterlace
+    {
+        /// both fields as they are, combing when drawing to the display
+        Weave => "weave",
+        /// only the field shown this frame, every line shown twice
+        Bob => "bob",
+    }
+}
+
+setting!
+{
+    /// How skinned meshes follow their skeleton.
+    Skinning
+    {
+        /// every vertex blends up to four joints
+        Smooth => "smooth",
+        /// every vertex follows only its heaviest joint, like PSX games
+        /// where each body part was moved as a whole
+        Rigid => "rigid",
+        /// the bind pose the mesh was modelled in
+        Off => "off",
+    }
+}
+
+setting!
+{
+    ColorDepth
+    {
+        /// 5 bits per channel like the PSX framebuffer
+        Rgb15 => "rgb15",
+        Rgb24 => "rgb24",
+    }
+}
+
+impl RenderMode
+{
+    /// Whether triangles are filled, as opposed to only drawing their
+    /// vertices or edges.
+    pub fn is_solid(self) -> bool
+    {
+        matches!(self, RenderMode::Flat | RenderMode::Gouraud | RenderMode::Textured)
+    }
+}
+
+impl Culling
+{
+    /// Whether a triangle with the given rasterizer::signed_area is dropped.
+    /// The models wind their front faces counterclockwise, which the camera
+    /// space (x right, y up, z forward) turns into clockwise on screen, i.e.
+    /// a positive area.
+    pub fn culls(self, signed_area: i64) -> bool
+    {
+        match self
+        {
+            Culling::None => false,
+            Culling::Back => signed_area < 0,
+            Culling::Front => signed_area > 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PreferencesError
+{
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl std::fmt::Display for PreferencesError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            PreferencesError::Io(error) => write!(f, "can't access render preferences: {}", error),
+            PreferencesError::Parse { line, message } => write!(f, "render preferences line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for PreferencesError {}
+
+impl From<std::io::Error> for PreferencesError
+{
+    fn from(error: std::io::Error) -> Self
+    {
+        PreferencesError::Io(error)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RenderPreferences
+{
+    /// framebuffer size in pixels, one the PSX had, see
+    /// display::HORIZONTAL_RESOLUTIONS and display::VERTICAL_RESOLUTIONS
+    pub resolution: (u32, u32),
+    /// scale the framebuffer to the window by whole lines, see
+    /// display::viewport
+    pub integer_scaling: bool,
+    pub render_mode: RenderMode,
+    /// draw triangle edges over the main pass
+    pub wireframe_overlay: bool,
+    /// draw vertices over the main pass
+    pub vertex_overlay: bool,
+    /// the PSX's 4x4 ordered dither when reducing to 15 bit color, has no
+    /// effect in 24 bit
+    pub dithering: bool,
+    /// round projected vertices to whole pixels like the GTE, otherwise they
+    /// keep rasterizer::SUBPIXEL_BITS of precision
+    pub vertex_snapping: bool,
+    pub texture_mapping: TextureMapping,
+    pub texture_filtering: TextureFiltering,
+    pub culling: Culling,
+    pub sort_mode: SortMode,
+    pub skinning: Skinning,
+    /// depth cueing like the GTE's DPCS: colors fade to fog_color between
+    /// fog_start and fog_end, in camera space depth
+    pub fog: bool,
+    pub fog_color: Color,
+    pub fog_start: FractionalType,
+    pub fog_end: FractionalType,
+    pub color_depth: ColorDepth,
+    /// show only every other line each frame, alternating between the even
+    /// and odd field like 480i, has no effect below 480 lines
+    pub interlaced: bool,
+    /// draw only the shown field's lines and keep the other field's from the
+    /// frame before, like drawing straight into the displayed VRAM area, so
+    /// motion combs; otherwise every frame draws both fields like into a
+    /// back buffer
+    pub draw_to_display: bool,
+    pub deinterlace: Deinterlace,
+}
+
+impl Default for RenderPreferences
+{
+    // as close to the console as we get
+    fn default() -> Self
+    {
+        Self {
+            resolution: (640, 480),
+            integer_scaling: true,
+            render_mode: RenderMode::Gouraud,
+            wireframe_overlay: false,
+            vertex_overlay: false,
+            dithering: true,
+            vertex_snapping: true,
+            texture_mapping: TextureMapping::Affine,
+            texture_filtering: TextureFiltering::Nearest,
+            culling: Culling::Back,
+            sort_mode: SortMode::Average,
+            skinning: Skinning::Smooth,
+            fog: false,
+            fog_color: Color::RGB(0, 0, 0),
+            fog_start: FractionalType::from_num(4),
+            fog_end: FractionalType::from_num(16),
+            color_depth: ColorDepth::Rgb15,
+            interlaced: false,
+            draw_to_display: true,
+            deinterlace: Deinterlace::Weave,
+        }
+    }
+}
+
+impl RenderPreferences
+{
+    /// The defaults with the settings in the file at `path` applied on top.
+    pub fn load(path: &std::path::Path) -> Result<Self, PreferencesError>
+    {
+        let mut preferences = Self::default();
+        preferences.apply_config(&std::fs::read_to_string(path)?)?;
+        Ok(preferences)
+    }
+
+    /// Loads `path` if it exists, falling back to the defaults otherwise. A
+    /// broken file is reported and ignored so the viewer still starts.
+    pub fn load_or_default(path: &std::path::Path) -> Self
+    {
+        match Self::load(path)
+        {
+            Ok(preferences) => preferences,
+            Err(PreferencesError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(error) =>
+            {
+                eprintln!("{}, using the default render preferences", error);
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes every setting to `path`, in the format load reads.
+    pub fn save(&self, path: &std::path::Path) -> Result<(), PreferencesError>
+    {
+        std::fs::write(path, self.to_config())?;
+        Ok(())
+    }
+
+    pub fn apply_config(&mut self, config: &str) -> Result<(), PreferencesError>
+    {
+        for (index, line) in config.lines().enumerate()
+        {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty()
+            {
+                continue;
+            }
+            line.split_once('=')
+                .ok_or_else(|| format!("expected `name = value`, got `{}`", line))
+                .and_then(|(name, value)| self.set(name.trim(), value.trim()))
+                .map_err(|message| PreferencesError::Parse { line: index + 1, message })?;
+        }
+        Ok(())
+    }
+
+    /// Applies the `--name=value` arguments and returns the rest, so other
+    /// flags can still be handled by the caller.
+    pub fn apply_args<I: IntoIterator<Item = String>>(&mut self, args: I) -> Result<Vec<String>, String>
+    {
+        let mut rest = Vec::new();
+        for arg in args
+        {
+            match arg.strip_prefix("--").and_then(|setting| setting.split_once('='))
+            {
+                Some((name, value)) => self.set(name, value).map_err(|message| format!("{}: {}", arg, message))?,
+                None => rest.push(arg),
+            }
+        }
+        Ok(rest)
+    }
+
+    /// Sets one setting from its config file name and value.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String>
+    {
+        fn parse_setting<T>(value: &str, from_name: fn(&str) -> Option<T>, all: &[T]) -> Result<T, String>
+            where T: std::fmt::Display
+        {
+            from_name(value).ok_or_else(|| {
+                let names: Vec<String> = all.iter().map(ToString::to_string).collect();
+                format!("expected one of {}, got `{}`", names.join(", "), value)
+            })
+        }
+
+        match name
+        {
+            "resolution" => self.resolution = parse_resolution(value)?,
+            "integer_scaling" => self.integer_scaling = parse_bool(value)?,
+            "render_mode" => self.render_mode = parse_setting(value, RenderMode::from_name, RenderMode::ALL)?,
+            "wireframe_overlay" => self.wireframe_overlay = parse_bool(value)?,
+            "vertex_overlay" => self.vertex_overlay = parse_bool(value)?,
+            "dithering" => self.dithering = parse_bool(value)?,
+            "vertex_snapping" => self.vertex_snapping = parse_bool(value)?,
+            "texture_mapping" => self.texture_mapping = parse_setting(value, TextureMapping::from_name, TextureMapping::ALL)?,
+            "texture_filtering" => self.texture_filtering = parse_setting(value, TextureFiltering::from_name, TextureFiltering::ALL)?,
+            "culling" => self.culling = parse_setting(value, Culling::from_name, Culling::ALL)?,
+            "sort_mode" => self.sort_mode = parse_setting(value, SortMode::from_name, SortMode::ALL)?,
+            "skinning" => self.skinning = parse_setting(value, Skinning::from_name, Skinning::ALL)?,
+            "fog" => self.fog = parse_bool(value)?,
+            "fog_color" => self.fog_color = parse_color(value)?,
+            "fog_start" => self.fog_start = parse_fractional(value)?,
+            "fog_end" => self.fog_end = parse_fractional(value)?,
+            "color_depth" => self.color_depth = parse_setting(value, ColorDepth::from_name, ColorDepth::ALL)?,
+            "interlaced" => self.interlaced = parse_bool(value)?,
+            "draw_to_display" => self.draw_to_display = parse_bool(value)?,
+            "deinterlace" => self.deinterlace = parse_setting(value, Deinterlace::from_name, Deinterlace::ALL)?,
+            _ => return Err(format!("unknown setting `{}`", name)),
+        }
+        Ok(())
+    }
+
+    pub fn to_config(&self) -> String
+    {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let Color { r, g, b, .. } = self.fog_color;
+        [
+            format!("resolution = {}x{}", self.resolution.0, self.resolution.1),
+            format!("integer_scaling = {}", on_off(self.integer_scaling)),
+            format!("render_mode = {}", self.render_mode),
+            format!("wireframe_overlay = {}", on_off(self.wireframe_overlay)),
+            format!("vertex_overlay = {}", on_off(self.vertex_overlay)),
+            format!("dithering = {}", on_off(self.dithering)),
+            format!("vertex_snapping = {}", on_off(self.vertex_snapping)),
+            format!("texture_mapping = {}", self.texture_mapping),
+            format!("texture_filtering = {}", self.texture_filtering),
+            format!("culling = {}", self.culling),
+            format!("sort_mode = {}", self.sort_mode),
+            format!("skinning = {}", self.skinning),
+            format!("fog = {}", on_off(self.fog)),
+            format!("fog_color = {}, {}, {}", r, g, b),
+            format!("fog_start = {}", self.fog_start),
+            format!("fog_end = {}", self.fog_end),
+            format!("color_depth = {}", self.color_depth),
+            format!("interlaced = {}", on_off(self.interlaced)),
+            format!("draw_to_display = {}", on_off(self.draw_to_display)),
+            format!("deinterlace = {}", self.deinterlace),
+        ]
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+    }
+
+    /// Whether frames are drawn one field at a time.
+    pub fn is_interlaced(&self) -> bool
+    {
+        self.interlaced && self.resolution.1 >= 480
+    }
+
+    /// How far a vertex at camera space `depth` has faded into the fog, from
+    /// 0 (not at all) to 255 (only fog).
+    pub fn fog_amount(&self, depth: FractionalType) -> u8
+    {
+        if !self.fog || self.fog_end <= self.fog_start
+        {
+            return 0;
+        }
+        let amount = ((depth - self.fog_start) / (self.fog_end - self.fog_start))
+            .clamp(FractionalType::zero(), FractionalType::one());
+        (amount * FractionalType::from_num(255)).round().to_num::<i32>() as u8
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String>
+{
+    match value
+    {
+        "on" | "true" => Ok(true),
+        "off" | "false" => Ok(false),
+        _ => Err(format!("expected on or off, got `{}`", value)),
+    }
+}
+
+// only the PSX's, which also keeps the framebuffer and the projection
+// within what a 16 bit integer part can address
+fn parse_resolution(value: &str) -> Result<(u32, u32), String>
+{
+    let error = || {
+        let widths: Vec<String> = display::HORIZONTAL_RESOLUTIONS.iter().map(ToString::to_string).collect();
+        let heights: Vec<String> = display::VERTICAL_RESOLUTIONS.iter().map(ToString::to_string).collect();
+        format!("expected a PSX resolution, {} wide by {} tall, got `{}`", widths.join(", "), heights.join(" or "), value)
+    };
+    let (width, height) = value.split_once('x').ok_or_else(error)?;
+    match (width.trim().parse(), height.trim().parse())
+    {
+        (Ok(width), Ok(height)) if display::HORIZONTAL_RESOLUTIONS.contains(&width) && display::VERTICAL_RESOLUTIONS.contains(&height) =>
+            Ok((width, height)),
+        _ => Err(error()),
+    }
+}
+
+fn parse_color(value: &str) -> Result<Color, String>
+{
+    let channels = value.split(',')
+        .map(|channel| channel.trim().parse::<u8>())
+        .collect::<Result<Vec<_>, _>>();
+    match channels.as_deref()
+    {
+        Ok(&[r, g, b]) => Ok(Color::RGB(r, g, b)),
+        _ => Err(format!("expected a color like 255, 128, 0, got `{}`", value)),
+    }
+}
+
+fn parse_fractional(value: &str) -> Result<FractionalType, String>
+{
+    value.parse().map_err(|_| format!("expected a number, got `{}`", value))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String>
+    {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    fn parse_error(config: &str) -> (usize, String)
+    {
+        match RenderPreferences::default().apply_config(config)
+        {
+            Err(PreferencesError::Parse { line, message }) => (line, message),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn config_sets_settings()
+    {
+        let mut preferences = RenderPreferences::default();
+        preferences.apply_config("
+            # closer to a modern GPU
+            resolution = 320 x 240
+            color_depth = rgb24   # no banding
+            texture_mapping = perspective
+            dithering = off
+            fog_color = 255, 128, 0
+            fog_end = 12.5
+        ").unwrap();
+        assert_eq!(preferences.resolution, (320, 240));
+        assert_eq!(preferences.color_depth, ColorDepth::Rgb24);
+        assert_eq!(preferences.texture_mapping, TextureMapping::Perspective);
+        assert!(!preferences.dithering);
+        assert_eq!(preferences.fog_color, Color::RGB(255, 128, 0));
+        assert_eq!(preferences.fog_end, FractionalType::from_num(12.5));
+        // settings missing from the file keep their defaults
+        assert_eq!(preferences.culling, RenderPreferences::default().culling);
+    }
+
+    #[test]
+    fn saved_config_loads_back()
+    {
+        let mut preferences = RenderPreferences::default();
+        preferences.apply_config("resolution = 368x240\nrender_mode = textured\nfog = on\ndeinterlace = bob").unwrap();
+        let mut loaded = RenderPreferences::default();
+        loaded.apply_config(&preferences.to_config()).unwrap();
+        assert_eq!(loaded, preferences);
+    }
+
+    #[test]
+    fn unknown_settings_and_bad_values_are_errors()
+    {
+        let (line, message) = parse_error("dithering = on\nbloom = on");
+        assert_eq!(line, 2);
+        assert!(message.contains("unknown setting `bloom`"), "{}", message);
+        let (_, message) = parse_error("dithering = maybe");
+        assert!(message.contains("expected on or off"), "{}", message);
+        let (_, message) = parse_error("culling = sideways");
+        assert!(message.contains("expected one of none, back, front"), "{}", message);
+        let (_, message) = parse_error("fog_color = 255, 128");
+        assert!(message.contains("expected a color"), "{}", message);
+        let (_, message) = parse_error("fog_start = far");
+        assert!(message.contains("expected a number"), "{}", message);
+    }
+
+    #[test]
+    fn only_psx_resolutions_are_accepted()
+    {
+        for resolution in ["256x240", "640x480", "368 x 480"]
+        {
+            assert!(parse_resolution(resolution).is_ok(), "{}", resolution);
+        }
+        for resolution in ["1920x1080", "320x200", "640", "640x", "x480", "-320x240"]
+        {
+            assert!(parse_resolution(resolution).is_err(), "{}", resolution);
+        }
+    }
+
+    #[test]
+    fn malformed_lines_are_errors()
+    {
+        let (line, message) = parse_error("\n# comment\ndithering on\n");
+        assert_eq!(line, 3);
+        assert!(message.contains("expected `name = value`"), "{}", message);
+    }
+
+    #[test]
+    fn arguments_override_the_config()
+    {
+        let mut preferences = RenderPreferences::default();
+        preferences.apply_config("culling = none\nfog = on\nresolution = 320x240").unwrap();
+        let rest = preferences.apply_args(args(&["--culling=front", "model.gltf", "--no-vsync", "--resolution=512x480"])).unwrap();
+        assert_eq!(preferences.culling, Culling::Front);
+        assert_eq!(preferences.resolution, (512, 480));
+        assert!(preferences.fog);
+        // everything that isn't --name=value is left to the caller
+        assert_eq!(rest, args(&["model.gltf", "--no-vsync"]));
+    }
+
+    #[test]
+    fn bad_arguments_are_errors()
+    {
+        let mut preferences = RenderPreferences::default();
+        let error = preferences.apply_args(args(&["--bloom=on"])).unwrap_err();
+        assert!(error.starts_with("--bloom=on: unknown setting"), "{}", error);
+        let error = preferences.apply_args(args(&["--culling=front", "--dithering=2"])).unwrap_err();
+        assert!(error.contains("expected on or off"), "{}", error);
+    }
+}
@@ -0,0 +1,256 @@
+use super::framebuffer::Framebuffer;
+use super::preferences::{ColorDepth, RenderPreferences, TextureMapping};
+use super::texture::{self, Texture};
+use crate::fractional_type::FractionalType;
+use crate::math::Vec2;
+use sdl2::pixels::Color;
+
+// Triangle rasterizer. Every pixel whose center is inside the triangle is
+// filled, found with edge functions over the triangle's bounding box. Pixels
+// exactly on an edge belong to the triangle only if it's a top or a left
+// edge, so triangles sharing an edge never fill a pixel twice or leave gaps
+// between them.
+//
+// Vertices are positioned with SUBPIXEL_BITS of precision, or snapped to
+// whole pixels, which is all the PSX GPU gets from the GTE. Colors are
+// interpolated linearly in screen space, texture coordinates either the same
+// way (affine, like the PSX) or perspective correct.
+
+/// Fractional bits of screen positions.
+pub const SUBPIXEL_BITS: u32 = 4;
+// fractional bits of the depth perspective correct texturing divides by
+const DEPTH_FRACTION_BITS: u32 = 8;
+// scale of the inverse depths, big enough to keep the far plane precise
+const INVERSE_DEPTH_ONE: i64 = 1 << 32;
+// vertex colors are multiplied with texels and divided by this, so 128 leaves
+// a texel as it is and anything above brightens it, like the PSX GPU
+const TEXTURE_MODULATION_NEUTRAL: i32 = 128;
+// the PSX GPU's ordered dither, added to each channel before cutting it to 5
+// bits, indexed by [y % 4][x % 4]
+const DITHER: [[i32; 4]; 4] = [
+    [-4, 0, -3, 1],
+    [2, -2, 3, -1],
+    [-3, 1, -4, 0],
+    [3, -1, 2, -2],
+];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ScreenVertex
+{
+    /// screen position with SUBPIXEL_BITS fractional bits
+    pub x: i32,
+    pub y: i32,
+    /// camera space depth, for perspective correct texturing
+    pub depth: FractionalType,
+    pub color: Color,
+    /// texture coordinates, 0 to 1 across the texture
+    pub uv: Vec2,
+    /// how far the vertex has faded into the fog, 0 to 255
+    pub fog: u8,
+}
+
+impl ScreenVertex
+{
+    /// A white vertex at a projected position, rounded to the nearest pixel
+    /// if `snap` is set.
+    pub fn new(position: Vec2, depth: FractionalType, snap: bool) -> Self
+    {
+        let coordinate = |value: FractionalType| if snap
+        {
+            (value.round().to_num::<i64>() << SUBPIXEL_BITS) as i32
+        }
+        else
+        {
+            to_fixed(value, SUBPIXEL_BITS) as i32
+        };
+        Self {
+            x: coordinate(position.x),
+            y: coordinate(position.y),
+            depth,
+            color: Color::RGB(255, 255, 255),
+            uv: Vec2::default(),
+            fog: 0,
+        }
+    }
+
+    /// The pixel the vertex is in.
+    pub fn pixel(&self) -> (i32, i32)
+    {
+        let half = 1 << (SUBPIXEL_BITS - 1);
+        ((self.x + half) >> SUBPIXEL_BITS, (self.y + half) >> SUBPIXEL_BITS)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum Shading<'a>
+{
+    /// one color for the whole triangle, the vertex colors are ignored
+    Flat(Color),
+    /// vertex colors interpolated across the triangle
+    Gouraud,
+    /// texels modulated by the interpolated vertex colors
+    Textured(&'a Texture),
+}
+
+/// Twice the triangle's area on screen in square subpixels, positive if the
+/// vertices run clockwise (with y pointing down) and zero if it's degenerate.
+pub fn signed_area(vertices: &[ScreenVertex; 3]) -> i64
+{
+    edge(&vertices[0], &vertices[1], vertices[2].x, vertices[2].y)
+}
+
+/// Fills a triangle of either winding. Returns false without drawing
+/// anything if it's degenerate, i.e. covers no area.
+pub fn draw_triangle(framebuffer: &mut Framebuffer, vertices: [ScreenVertex; 3], shading: Shading, preferences: &RenderPreferences) -> bool
+{
+    let [v0, mut v1, mut v2] = vertices;
+    let mut area = signed_area(&vertices);
+    if area == 0
+    {
+        return false;
+    }
+    if area < 0
+    {
+        std::mem::swap(&mut v1, &mut v2);
+        area = -area;
+    }
+    let vertices = [v0, v1, v2];
+
+    // the pixels whose centers can be inside
+    let to_pixel_min = |value: i32| (value + (1 << SUBPIXEL_BITS) - 1) >> SUBPIXEL_BITS;
+    let to_pixel_max = |value: i32| value >> SUBPIXEL_BITS;
+    let min_x = to_pixel_min(vertices.iter().map(|v| v.x).min().unwrap_or(0)).max(0);
+    let max_x = to_pixel_max(vertices.iter().map(|v| v.x).max().unwrap_or(0)).min(framebuffer.width() as i32 - 1);
+    let min_y = to_pixel_min(vertices.iter().map(|v| v.y).min().unwrap_or(0)).max(0);
+    let max_y = to_pixel_max(vertices.iter().map(|v| v.y).max().unwrap_or(0)).min(framebuffer.height() as i32 - 1);
+    if min_x > max_x || min_y > max_y
+    {
+        return true;
+    }
+
+    // edge i is opposite vertex i, so its edge function is vertex i's weight
+    let edges = [(&v1, &v2), (&v2, &v0), (&v0, &v1)];
+    // moving one pixel right or down changes an edge function by these
+    let step_x = edges.map(|(a, b)| -((b.y - a.y) as i64) << SUBPIXEL_BITS);
+    let step_y = edges.map(|(a, b)| ((b.x - a.x) as i64) << SUBPIXEL_BITS);
+    let bias = edges.map(|(a, b)| if is_top_left(a, b) { 0 } else { -1 });
+    let mut row = edges.map(|(a, b)| edge(a, b, min_x << SUBPIXEL_BITS, min_y << SUBPIXEL_BITS));
+
+    let texels = match shading
+    {
+        Shading::Textured(texture) => vertices.map(|v| (
+            to_fixed(v.uv.x * FractionalType::from_num(texture.width()), texture::FRACTION_BITS),
+            to_fixed(v.uv.y * FractionalType::from_num(texture.height()), texture::FRACTION_BITS)
+        )),
+        _ => [(0, 0); 3],
+    };
+    let inverse_depths = vertices.map(|v| INVERSE_DEPTH_ONE / to_fixed(v.depth, DEPTH_FRACTION_BITS).max(1));
+    let fogged = vertices.iter().any(|v| v.fog > 0);
+
+    for y in min_y..=max_y
+    {
+        let mut weights = row;
+        for x in min_x..=max_x
+        {
+            if (0..3).all(|i| weights[i] + bias[i] >= 0)
+            {
+                let interpolate = |values: [i64; 3]| (0..3).map(|i| values[i] * weights[i]).sum::<i64>() / area;
+                let channel = |channel: fn(&Color) -> u8| interpolate(vertices.map(|v| channel(&v.color) as i64)) as i32;
+                let mut color = match shading
+                {
+                    Shading::Flat(color) => color,
+                    Shading::Gouraud => Color::RGB(
+                        channel(|c| c.r) as u8,
+                        channel(|c| c.g) as u8,
+                        channel(|c| c.b) as u8
+                    ),
+                    Shading::Textured(texture) =>
+                    {
+                        let (u, v) = match preferences.texture_mapping
+                        {
+                            TextureMapping::Affine => (interpolate(texels.map(|(u, _)| u)), interpolate(texels.map(|(_, v)| v))),
+                            TextureMapping::Perspective =>
+                            {
+                                // interpolate uv / z and 1 / z, then divide
+                                let perspective_weights = (0..3).map(|i| weights[i] as i128 * inverse_depths[i] as i128);
+                                let total: i128 = perspective_weights.clone().sum();
+                                let interpolate = |values: [i64; 3]| perspective_weights.clone()
+                                    .zip(values)
+                                    .map(|(weight, value)| weight * value as i128)
+                                    .sum::<i128>() / total.max(1);
+                                (interpolate(texels.map(|(u, _)| u)) as i64, interpolate(texels.map(|(_, v)| v)) as i64)
+                            }
+                        };
+                        let texel = texture.sample(u, v, preferences.texture_filtering);
+                        let modulate = |texel: u8, shade: i32| (texel as i32 * shade / TEXTURE_MODULATION_NEUTRAL).min(255) as u8;
+                        Color::RGB(
+                            modulate(texel.r, channel(|c| c.r)),
+                            modulate(texel.g, channel(|c| c.g)),
+                            modulate(texel.b, channel(|c| c.b))
+                        )
+                    }
+                };
+                if fogged
+                {
+                    let fog = interpolate(vertices.map(|v| v.fog as i64)) as i32;
+                    let mix = |channel: u8, fog_channel: u8| (channel as i32 + (fog_channel as i32 - channel as i32) * fog / 255) as u8;
+                    let fog_color = preferences.fog_color;
+                    color = Color::RGB(mix(color.r, fog_color.r), mix(color.g, fog_color.g), mix(color.b, fog_color.b));
+                }
+                framebuffer.put_pixel(x, y, to_color_depth(color, x, y, preferences));
+            }
+            for i in 0..3
+            {
+                weights[i] += step_x[i];
+            }
+        }
+        for i in 0..3
+        {
+            row[i] += step_y[i];
+        }
+    }
+    true
+}
+
+// twice the signed area of (a, b, p), positive if p is on the inner side of
+// a -> b for a triangle with positive area
+fn edge(a: &ScreenVertex, b: &ScreenVertex, x: i32, y: i32) -> i64
+{
+    (b.x - a.x) as i64 * (y - a.y) as i64 - (b.y - a.y) as i64 * (x - a.x) as i64
+}
+
+// with y pointing down and positive area, a top edge runs exactly to the
+// right and a left edge runs up
+fn is_top_left(a: &ScreenVertex, b: &ScreenVertex) -> bool
+{
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    (dy == 0 && dx > 0) || dy < 0
+}
+
+// reduces a color to what the framebuffer can store, dithered if enabled
+fn to_color_depth(color: Color, x: i32, y: i32, preferences: &RenderPreferences) -> Color
+{
+    match preferences.color_depth
+    {
+        ColorDepth::Rgb24 => color,
+        ColorDepth::Rgb15 =>
+        {
+            let offset = if preferences.dithering { DITHER[(y & 3) as usize][(x & 3) as usize] } else { 0 };
+            // back to 8 bits the way the PSX's video DAC does
+            let quantize = |channel: u8| {
+                let channel = ((channel as i32 + offset).clamp(0, 255) >> 3) as u8;
+                (channel << 3) | (channel >> 2)
+            };
+            Color::RGB(quantize(color.r), quantize(color.g), quantize(color.b))
+        }
+    }
+}
+
+// a value with `bits` fractional bits, split into whole and fractional part so
+// the scaling can't overflow narrow fixed point types
+fn to_fixed(value: FractionalType, bits: u32) -> i64
+{
+    let whole = value.floor();
+    let fraction = ((value - whole) * FractionalType::from_num(1 << bits)).to_num::<i64>();
+    (whole.to_num::<i64>() << bits) + fraction
+}
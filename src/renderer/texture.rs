@@ -0,0 +1,82 @@
+use super::preferences::TextureFiltering;
+use sdl2::pixels::Color;
+
+// Textures are plain RGB texel arrays, sampled with nearest neighbour like the
+// PSX GPU does or bilinear for comparison. Texture coordinates wrap around,
+// with (0, 0) at the top left corner as in glTF.
+
+/// Fractional bits of the texel coordinates passed to Texture::sample.
+pub const FRACTION_BITS: u32 = 8;
+
+pub struct Texture
+{
+    width: u32,
+    height: u32,
+    texels: Vec<Color>,
+}
+
+impl Texture
+{
+    pub fn new(width: u32, height: u32, texels: Vec<Color>) -> Self
+    {
+        assert_eq!(texels.len(), width as usize * height as usize, "texture size doesn't match its texels");
+        Self { width, height, texels }
+    }
+
+    /// A checkerboard of `square` by `square` texel squares, used for models
+    /// without a texture of their own.
+    pub fn checkerboard(size: u32, square: u32, even: Color, odd: Color) -> Self
+    {
+        let texels = (0..size * size)
+            .map(|index| if (index % size / square + index / size / square).is_multiple_of(2) { even } else { odd })
+            .collect();
+        Self::new(size, size, texels)
+    }
+
+    pub fn width(&self) -> u32
+    {
+        self.width
+    }
+
+    pub fn height(&self) -> u32
+    {
+        self.height
+    }
+
+    /// The texel at integer texel coordinates, wrapping around.
+    pub fn texel(&self, u: i32, v: i32) -> Color
+    {
+        let u = u.rem_euclid(self.width as i32) as usize;
+        let v = v.rem_euclid(self.height as i32) as usize;
+        self.texels[v * self.width as usize + u]
+    }
+
+    /// Samples at texel coordinates with FRACTION_BITS fractional bits.
+    /// Texel centers are at .5 like pixel centers.
+    pub fn sample(&self, u: i64, v: i64, filtering: TextureFiltering) -> Color
+    {
+        match filtering
+        {
+            TextureFiltering::Nearest => self.texel((u >> FRACTION_BITS) as i32, (v >> FRACTION_BITS) as i32),
+            TextureFiltering::Bilinear =>
+            {
+                let half = 1 << (FRACTION_BITS - 1);
+                let (u, v) = (u - half, v - half);
+                let (x, y) = ((u >> FRACTION_BITS) as i32, (v >> FRACTION_BITS) as i32);
+                let one = 1 << FRACTION_BITS;
+                let (fx, fy) = (u & (one - 1), v & (one - 1));
+                let corners = [
+                    (self.texel(x, y), (one - fx) * (one - fy)),
+                    (self.texel(x + 1, y), fx * (one - fy)),
+                    (self.texel(x, y + 1), (one - fx) * fy),
+                    (self.texel(x + 1, y + 1), fx * fy),
+                ];
+                let channel = |channel: fn(&Color) -> u8| {
+                    let sum: i64 = corners.iter().map(|(texel, weight)| channel(texel) as i64 * weight).sum();
+                    (sum >> (2 * FRACTION_BITS)) as u8
+                };
+                Color::RGB(channel(|c| c.r), channel(|c| c.g), channel(|c| c.b))
+            }
+        }
+    }
+}
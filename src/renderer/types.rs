@@ -0,0 +1,139 @@
+use crate::fractional_type::FractionalType;
+use crate::math::{Vec2, Vec3};
+use sdl2::pixels::Color;
+use std::collections::HashMap;
+
+// The renderer's own mesh representation. Model formats are converted into it
+// at load time, so the pipeline doesn't care where a mesh came from.
+
+// vertices without a color of their own
+pub const DEFAULT_VERTEX_COLOR: Color = Color::RGB(200, 200, 200);
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Vertex
+{
+    pub position: Vec3,
+    /// unit length, or zero if unknown
+    pub normal: Vec3,
+    /// texture coordinates with (0, 0) at the top left of the texture
+    pub uv: Vec2,
+    pub color: Color,
+}
+
+/// The joints moving a vertex of a skinned mesh, up to four like glTF's
+/// JOINTS_0 and WEIGHTS_0.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct JointWeights
+{
+    /// indices into the joints of the skin deforming the mesh
+    pub joints: [u16; 4],
+    /// adding up to one, unused joints weigh zero
+    pub weights: [FractionalType; 4],
+}
+
+impl JointWeights
+{
+    /// The joint with the largest weight.
+    pub fn heaviest(&self) -> u16
+    {
+        (0..4).max_by_key(|&index| self.weights[index])
+            .map_or(0, |index| self.joints[index])
+    }
+}
+
+/// Indexed triangle mesh. A vertex is shared only by corners that agree on
+/// every attribute, like in glTF.
+#[derive(Clone, Default, Debug)]
+pub struct Mesh
+{
+    pub vertices: Vec<Vertex>,
+    pub triangles: Vec<[usize; 3]>,
+    /// index into the textures of the model the mesh belongs to
+    pub texture: Option<usize>,
+    /// one per vertex if the mesh can be skinned, empty otherwise
+    pub joint_weights: Vec<JointWeights>,
+}
+
+impl Mesh
+{
+    /// Converts an OBJ model, splitting polygons into triangle fans
+    /// (v_0, v_(i - 1), v_i). OBJ indexes every attribute separately, so each
+    /// distinct (position, uv, normal) combination becomes one vertex.
+    /// Corners without a normal get the normal of their face.
+    pub fn from_obj(obj: &obj::ObjData) -> Self
+    {
+        let mut mesh = Self::default();
+        let mut vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+        for object in &obj.objects
+        {
+            for group in &object.groups
+            {
+                for polygon in &group.polys
+                {
+                    let corners: Vec<&obj::IndexTuple> = polygon.0.iter().collect();
+                    if corners.len() < 3
+                    {
+                        continue;
+                    }
+                    let position = |corner: &obj::IndexTuple| Vec3::from_f32(&obj.position[corner.0]);
+                    let face_normal = triangle_normal(position(corners[0]), position(corners[1]), position(corners[2]));
+                    let mut vertex_index = |corner: &obj::IndexTuple| *vertex_indices
+                        .entry((corner.0, corner.1, corner.2))
+                        .or_insert_with(|| {
+                            mesh.vertices.push(Vertex {
+                                position: position(corner),
+                                normal: corner.2.map_or(face_normal, |normal| Vec3::from_f32(&obj.normal[normal]).normalize()),
+                                // OBJ has v pointing up
+                                uv: corner.1.map_or(Vec2::default(), |uv| Vec2::new(
+                                    FractionalType::from_num(obj.texture[uv][0]),
+                                    FractionalType::from_num(1.0 - obj.texture[uv][1])
+                                )),
+                                color: DEFAULT_VERTEX_COLOR,
+                            });
+                            mesh.vertices.len() - 1
+                        });
+                    let first = vertex_index(corners[0]);
+                    let mut previous = vertex_index(corners[1]);
+                    for corner in &corners[2..]
+                    {
+                        let current = vertex_index(corner);
+                        mesh.triangles.push([first, previous, current]);
+                        previous = current;
+                    }
+                }
+            }
+        }
+        mesh
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = &Vec3> + Clone
+    {
+        self.vertices.iter().map(|vertex| &vertex.position)
+    }
+
+    /// Gives every vertex the average normal of the triangles around it, for
+    /// models that come without normals.
+    pub fn compute_normals(&mut self)
+    {
+        let mut sums = vec![Vec3::zero(); self.vertices.len()];
+        for triangle in &self.triangles
+        {
+            let [a, b, c] = triangle.map(|index| self.vertices[index].position);
+            let normal = triangle_normal(a, b, c);
+            for &index in triangle
+            {
+                sums[index] += normal;
+            }
+        }
+        for (vertex, sum) in self.vertices.iter_mut().zip(sums)
+        {
+            vertex.normal = sum.normalize();
+        }
+    }
+}
+
+/// The unit normal of a counterclockwise triangle.
+pub fn triangle_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3
+{
+    (b - a).cross(c - a).normalize()
+}